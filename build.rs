@@ -0,0 +1,19 @@
+fn main() {
+    // Regenerate the C header, so it always matches src/capi.rs.
+    #[cfg(feature = "capi")]
+    {
+        println!("cargo:rerun-if-changed=src/capi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        let dir = std::env::var("CARGO_MANIFEST_DIR").expect("Cargo sets the manifest dir.");
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", dir))
+            .expect("cbindgen.toml is readable.");
+        cbindgen::Builder::new()
+            .with_crate(&dir)
+            .with_config(config)
+            .generate()
+            .expect("The C interface can be turned into a header.")
+            .write_to_file(format!("{}/include/laser_puzzle.h", dir));
+    }
+    #[cfg(not(feature = "capi"))]
+    println!("cargo:rerun-if-changed=build.rs");
+}
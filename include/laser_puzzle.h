@@ -0,0 +1,172 @@
+/*
+ * The laser puzzle engine. Link against liblaser_puzzle.
+ *
+ * Grids and observations are opaque handles. Release them with lp_grid_free and
+ * lp_observations_free, and don't use them afterwards. Pointers to results must point to
+ * enough writable memory. Cells are counted from the top left, x to the right and y down.
+ */
+
+#ifndef LASER_PUZZLE_H
+#define LASER_PUZZLE_H
+
+/* Generated from src/capi.rs by cbindgen when building with --features capi. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The variant of the laser rules, see [Ruleset].
+typedef enum LpRules {
+  // A laser entering next to an atom on the edge is reflected.
+  LP_RULES_CLASSIC,
+  // A single atom next to the entry turns the laser away, which looks like an absorption.
+  LP_RULES_PLAIN,
+} LpRules;
+
+// A side of the box, where a laser is shone in or comes out.
+typedef enum LpSide {
+  // The top side, a laser shone in from here moves down.
+  LP_SIDE_TOP,
+  // The bottom side.
+  LP_SIDE_BOTTOM,
+  // The left side.
+  LP_SIDE_LEFT,
+  // The right side.
+  LP_SIDE_RIGHT,
+} LpSide;
+
+// How a probe ended.
+typedef enum LpOutcome {
+  // The laser never came out.
+  LP_OUTCOME_ABSORBED,
+  // The laser came out where it went in.
+  LP_OUTCOME_REFLECTED,
+  // The laser came out somewhere else.
+  LP_OUTCOME_EXIT,
+} LpOutcome;
+
+// What is known about a cell, written by [lp_solve].
+typedef enum LpKnowledge {
+  // Could be an atom or empty.
+  LP_KNOWLEDGE_UNKNOWN,
+  // Holds an atom.
+  LP_KNOWLEDGE_ATOM,
+  // Is empty.
+  LP_KNOWLEDGE_EMPTY,
+} LpKnowledge;
+
+// The difficulty of generated puzzles, see [Difficulty].
+typedef enum LpDifficulty {
+  // Whatever comes up first.
+  LP_DIFFICULTY_ANY,
+  // See [Difficulty::Easy].
+  LP_DIFFICULTY_EASY,
+  // See [Difficulty::Medium].
+  LP_DIFFICULTY_MEDIUM,
+  // See [Difficulty::Hard].
+  LP_DIFFICULTY_HARD,
+} LpDifficulty;
+
+// The atoms hidden in the box.
+typedef struct LpGrid LpGrid;
+
+// What the player sees on the sides of the box.
+typedef struct LpObservations LpObservations;
+
+// The result of shining a laser into the box.
+typedef struct LpProbe {
+  // How the probe ended.
+  enum LpOutcome outcome;
+  // Where the laser came out, or where it went in if it didn't.
+  enum LpSide side;
+  // Row or column on that side, counted from the top left.
+  uint8_t shift;
+} LpProbe;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// An empty box.
+struct LpGrid *lp_grid_new(void);
+
+// A box with atoms in random cells, the same ones for the same seed. `NULL` for more atoms than
+// cells.
+struct LpGrid *lp_grid_random(uint8_t atoms, uint64_t seed);
+
+// A box from a number as printed by the command line, see [AtomGrid::as_bitboard].
+struct LpGrid *lp_grid_from_bitboard(uint64_t bitboard);
+
+// Releases a grid. Does nothing for `NULL`.
+void lp_grid_free(struct LpGrid *grid);
+
+// The grid as a number, see [AtomGrid::as_bitboard].
+uint64_t lp_grid_bitboard(const struct LpGrid *grid);
+
+// Whether there is an atom in column `x` and row `y`, counted from the top left.
+bool lp_grid_get(const struct LpGrid *grid, int32_t x, int32_t y);
+
+// Puts an atom into a cell or takes it out. Returns `false` for cells outside the box.
+bool lp_grid_set(struct LpGrid *grid, int32_t x, int32_t y, bool atom);
+
+// The number of atoms in the grid.
+uint32_t lp_grid_atom_count(const struct LpGrid *grid);
+
+// Shines a laser into the grid and writes what happened to `result`.
+bool lp_probe(const struct LpGrid *grid,
+              enum LpRules rules,
+              enum LpSide side,
+              uint8_t shift,
+              struct LpProbe *result);
+
+// Observations without any probes yet.
+struct LpObservations *lp_observations_new(enum LpRules rules);
+
+// The observations of a laser shone in from every position on the border.
+struct LpObservations *lp_observations_observe_all(const struct LpGrid *grid, enum LpRules rules);
+
+// Releases observations. Does nothing for `NULL`.
+void lp_observations_free(struct LpObservations *observations);
+
+// Shines a laser into the grid and records the result on both sides where it went in and out.
+// Probing a position which already shows a result changes nothing.
+bool lp_observations_probe(struct LpObservations *observations,
+                           const struct LpGrid *grid,
+                           enum LpSide side,
+                           uint8_t shift);
+
+// The symbol on the side as a Unicode code point: `?` if not probed yet, `×` (U+00D7) for an
+// absorption, `⇄` (U+21C4) for a reflection, or a capital letter which appears on the sides
+// twice. Returns 0 for a shift out of range.
+uint32_t lp_observations_get(const struct LpObservations *observations,
+                             enum LpSide side,
+                             uint8_t shift);
+
+// Writes what the rule based solver finds out to `knowledge`, 64 cells row by row from the top
+// left. `knowledge` must have room for 64 values. Returns `false` if the solver failed, then
+// `knowledge` is left unchanged.
+bool lp_solve(const struct LpObservations *observations, enum LpKnowledge *knowledge);
+
+// Finds up to `capacity` grids with `atoms` atoms which explain the observations, and writes
+// them to `solutions` as bitboards. Returns how many were found; ask for two to learn whether
+// the solution is unique. `solutions` must have room for `capacity` values. Returns 0 if the
+// search failed.
+uintptr_t lp_find_solutions(const struct LpObservations *observations,
+                            uint8_t atoms,
+                            uint64_t *solutions,
+                            uintptr_t capacity);
+
+// A random grid whose fully probed puzzle has exactly one solution, the same one for the same
+// seed. Probe it with [lp_observations_observe_all] to get the puzzle. Returns `NULL` if none was
+// found, e.g. for too many atoms.
+struct LpGrid *lp_generate(uint8_t atoms,
+                           enum LpRules rules,
+                           enum LpDifficulty difficulty,
+                           uint64_t seed);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* LASER_PUZZLE_H */
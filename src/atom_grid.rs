@@ -1,70 +1,130 @@
+//! The atoms hidden in the box, the secret the player has to find out.
+
 use crate::i8vec2::I8Vec2;
+use rand::Rng;
 use std::fmt::{Display, Formatter};
 
+/// Width and height of the box.
 pub const GRID_SIZE: usize = 8;
 
 /// The hidden inner secret of the game
-#[derive(Default, Debug, PartialEq, Eq, Clone)]
+///
+/// Stored as a bitboard with one bit per cell, the cell (x, y) is bit `y * GRID_SIZE + x`.
+#[derive(Default, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
 pub struct AtomGrid {
-    atoms: [[bool; GRID_SIZE]; GRID_SIZE],
+    bits: u64,
+}
+
+fn mask(v: I8Vec2) -> u64 {
+    1 << (v.y as usize * GRID_SIZE + v.x as usize)
 }
 
 impl AtomGrid {
+    /// Whether there is an atom. Cells outside the grid never have one.
     pub fn get(&self, v: I8Vec2) -> bool {
-        v.in_grid() && self.atoms[v.x as usize][v.y as usize]
+        v.in_grid() && self.bits & mask(v) != 0
     }
+    /// Puts an atom into a cell or takes it out. Panics for cells outside the grid.
     pub fn set(&mut self, v: I8Vec2, value: bool) {
         if v.in_grid() {
-            self.atoms[v.x as usize][v.y as usize] = value;
+            if value {
+                self.bits |= mask(v);
+            } else {
+                self.bits &= !mask(v);
+            }
         } else {
             panic!("Out of bounds. Writing {} to {:?}", value, v);
         }
     }
 
+    /// A grid with atoms in random cells.
     pub fn random(atom_count: u8) -> Self {
+        Self::random_with(atom_count, &mut rand::thread_rng())
+    }
+
+    /// Like [AtomGrid::random], but with a caller provided source of randomness. Use a seeded rng
+    /// to get reproducible grids. Panics for more atoms than cells.
+    pub fn random_with<R: Rng>(atom_count: u8, rng: &mut R) -> Self {
+        assert!(
+            atom_count as usize <= GRID_SIZE * GRID_SIZE,
+            "{} atoms don't fit into the grid",
+            atom_count
+        );
         let mut this = Self::default();
-        let mut placed_down = 0;
-        while placed_down < atom_count {
-            let v = I8Vec2::random();
-            if !this.get(v) {
-                this.set(v, true);
-                placed_down += 1;
-            }
+        while this.atom_count() < atom_count as u32 {
+            this.set(I8Vec2::random_with(rng), true);
         }
         this
     }
 
-    pub fn as_bitboard(&self) -> u64 {
-        let mut result = 0;
-        for y in 0..8 {
-            for x in 0..8 {
-                result <<= 1;
-                if self.atoms[x][y] {
-                    result |= 1;
-                }
+    /// The number of atoms.
+    pub fn atom_count(&self) -> u32 {
+        self.bits.count_ones()
+    }
+
+    /// All positions with an atom, row by row from the top left.
+    pub fn atoms(&self) -> impl Iterator<Item = I8Vec2> {
+        let mut bits = self.bits;
+        std::iter::from_fn(move || {
+            if bits == 0 {
+                return None;
             }
+            let i = bits.trailing_zeros() as usize;
+            bits &= bits - 1;
+            Some(I8Vec2::new((i % GRID_SIZE) as i8, (i / GRID_SIZE) as i8))
+        })
+    }
+
+    /// Atoms which are in either grid.
+    pub fn union(&self, other: &AtomGrid) -> AtomGrid {
+        AtomGrid {
+            bits: self.bits | other.bits,
+        }
+    }
+
+    /// Atoms which are in both grids.
+    pub fn intersection(&self, other: &AtomGrid) -> AtomGrid {
+        AtomGrid {
+            bits: self.bits & other.bits,
         }
-        result
     }
 
+    /// Atoms which are in this grid, but not in the other one.
+    pub fn difference(&self, other: &AtomGrid) -> AtomGrid {
+        AtomGrid {
+            bits: self.bits & !other.bits,
+        }
+    }
+
+    /// The native bit layout, see [AtomGrid]. Use [AtomGrid::as_bitboard] to store grids.
+    pub fn bits(&self) -> u64 {
+        self.bits
+    }
+
+    /// The inverse of [AtomGrid::bits].
+    pub fn from_bits(bits: u64) -> Self {
+        AtomGrid { bits }
+    }
+
+    /// The grid as a number for printing and storing. Unlike [AtomGrid::bits], the top left cell
+    /// is the highest bit here. We keep this layout so all bitboards written so far stay valid.
+    pub fn as_bitboard(&self) -> u64 {
+        self.bits.reverse_bits()
+    }
+
+    /// The inverse of [AtomGrid::as_bitboard].
     pub fn from_bitboard(bitboard: u64) -> Self {
-        let mut this = Self::default();
-        let mut bitboard = bitboard;
-        for y in 0..8 {
-            for x in 0..8 {
-                this.atoms[7 - x][7 - y] = bitboard & 1 == 1;
-                bitboard >>= 1;
-            }
+        AtomGrid {
+            bits: bitboard.reverse_bits(),
         }
-        this
     }
 }
 
 impl Display for AtomGrid {
     fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
-        for y in 0..8 {
-            for x in 0..8 {
-                if self.atoms[x][y] {
+        for y in 0..GRID_SIZE {
+            for x in 0..GRID_SIZE {
+                if self.get(I8Vec2::new(x as i8, y as i8)) {
                     f.write_str(" o")?;
                 } else {
                     f.write_str(" .")?;
@@ -76,6 +136,17 @@ impl Display for AtomGrid {
     }
 }
 
+/// Random grids for property tests. Failing grids shrink to fewer atoms in the top left.
+#[cfg(test)]
+pub(crate) fn arbitrary_grid(
+    atoms: std::ops::RangeInclusive<usize>,
+) -> impl proptest::strategy::Strategy<Value = AtomGrid> {
+    use proptest::strategy::Strategy;
+    proptest::collection::btree_set(0..GRID_SIZE * GRID_SIZE, atoms).prop_map(|cells| AtomGrid {
+        bits: cells.into_iter().fold(0, |bits, i| bits | 1 << i),
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -90,4 +161,47 @@ mod test {
             assert_eq!(grid, grid2);
         }
     }
+
+    /// The bitboard layout must not change, bitboards are stored in tests and puzzle collections.
+    #[test]
+    fn test_bitboard_layout() {
+        let mut grid = AtomGrid::default();
+        grid.set(I8Vec2::new(0, 0), true);
+        assert_eq!(grid.as_bitboard(), 1 << 63);
+        grid.set(I8Vec2::new(7, 7), true);
+        assert_eq!(grid.as_bitboard(), (1 << 63) | 1);
+    }
+
+    #[test]
+    fn test_atoms_and_set_operations() {
+        let grid = AtomGrid::random(5);
+        assert_eq!(grid.atom_count(), 5);
+        let atoms: Vec<I8Vec2> = grid.atoms().collect();
+        assert_eq!(atoms.len(), 5);
+        assert!(atoms.iter().all(|&v| grid.get(v)));
+
+        let other = AtomGrid::random(5);
+        let union = grid.union(&other);
+        let intersection = grid.intersection(&other);
+        assert_eq!(
+            union.atom_count() + intersection.atom_count(),
+            grid.atom_count() + other.atom_count()
+        );
+        assert_eq!(grid.difference(&other).union(&intersection), grid);
+        assert_eq!(
+            grid.difference(&other).intersection(&other),
+            AtomGrid::default()
+        );
+    }
+
+    #[test]
+    fn a_full_grid_fits() {
+        assert_eq!(AtomGrid::random(64).atom_count(), 64);
+    }
+
+    #[test]
+    #[should_panic]
+    fn more_atoms_than_cells_dont_fit() {
+        AtomGrid::random(65);
+    }
 }
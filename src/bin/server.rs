@@ -0,0 +1,34 @@
+//! Serves games on localhost, see `laser_puzzle::server` for the requests it answers.
+
+use clap::Parser;
+use laser_puzzle::server::{self, Games};
+
+#[derive(Parser)]
+#[command(
+    name = "laser-puzzle-server",
+    about = "Play black box laser puzzles over HTTP on localhost."
+)]
+struct Args {
+    /// The port to listen on.
+    #[arg(long, default_value_t = 8642)]
+    port: u16,
+    /// Seed for reproducible games. A random seed is used otherwise.
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let server = match tiny_http::Server::http(("127.0.0.1", args.port)) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(2);
+        }
+    };
+    eprintln!("Listening on http://{}", server.server_addr());
+    server::serve(
+        &server,
+        &mut Games::new(args.seed.unwrap_or_else(rand::random)),
+    );
+}
@@ -0,0 +1,228 @@
+//! A fast path for probing, working directly on the 64 bit representation of the grid.
+//!
+//! Instead of asking the grid about every neighbour in every step, we shift the atoms once per
+//! direction to find all cells which have an atom in front, front-left or front-right. Together
+//! with precomputed rays, the laser then jumps straight to the next cell where something happens.
+//! The results are the same as with [LaserTip::traverse_grid] and [crate::observation::shoot],
+//! which stay the reference implementation.
+//!
+//! Bits are indexed by `y * GRID_SIZE + x`, just like in [AtomGrid].
+
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::i8vec2::I8Vec2;
+use crate::laser::{Direction, LaserTip, Ruleset};
+use crate::observation::ProbeResult;
+
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+const FILE_H: u64 = FILE_A << (GRID_SIZE - 1);
+
+/// Every laser which stops more often than there are positions and directions must run in circles.
+const MAX_MOVES: usize = 4 * GRID_SIZE * GRID_SIZE;
+
+/// Moves every bit by the given offset of at most one step in each direction. Bits which would
+/// leave the grid are dropped.
+fn shift(bits: u64, v: I8Vec2) -> u64 {
+    let bits = match v.x {
+        1 => (bits << 1) & !FILE_A,
+        -1 => (bits >> 1) & !FILE_H,
+        _ => bits,
+    };
+    match v.y {
+        1 => bits << GRID_SIZE,
+        -1 => bits >> GRID_SIZE,
+        _ => bits,
+    }
+}
+
+fn index(v: I8Vec2) -> usize {
+    v.y as usize * GRID_SIZE + v.x as usize
+}
+
+fn bit(v: I8Vec2) -> u64 {
+    1 << index(v)
+}
+
+/// For each direction and cell, the cell itself and all cells behind it in that direction.
+static RAYS: [[u64; GRID_SIZE * GRID_SIZE]; 4] = rays();
+
+const fn rays() -> [[u64; GRID_SIZE * GRID_SIZE]; 4] {
+    let mut rays = [[0; GRID_SIZE * GRID_SIZE]; 4];
+    let mut i = 0;
+    while i < GRID_SIZE * GRID_SIZE {
+        let (x, y) = (i % GRID_SIZE, i / GRID_SIZE);
+        let mut j = 0;
+        while j < GRID_SIZE {
+            if j <= y {
+                rays[Direction::Up as usize][i] |= 1 << (j * GRID_SIZE + x);
+            }
+            if j >= y {
+                rays[Direction::Down as usize][i] |= 1 << (j * GRID_SIZE + x);
+            }
+            if j <= x {
+                rays[Direction::Left as usize][i] |= 1 << (y * GRID_SIZE + j);
+            }
+            if j >= x {
+                rays[Direction::Right as usize][i] |= 1 << (y * GRID_SIZE + j);
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    rays
+}
+
+/// The atoms of a grid together with neighbour masks for each laser direction.
+pub struct BitboardProbe {
+    atoms: u64,
+    /// Cells with an atom right in front, indexed by direction.
+    front: [u64; 4],
+    /// Cells with an atom on the front-left corner.
+    front_left: [u64; 4],
+    /// Cells with an atom on the front-right corner.
+    front_right: [u64; 4],
+    /// Cells where a laser does not just move forward, the union of the three masks above.
+    events: [u64; 4],
+}
+
+impl BitboardProbe {
+    pub fn new(grid: &AtomGrid) -> Self {
+        let atoms = grid.bits();
+        let mut this = BitboardProbe {
+            atoms,
+            front: [0; 4],
+            front_left: [0; 4],
+            front_right: [0; 4],
+            events: [0; 4],
+        };
+        for d in Direction::all() {
+            let back = d.flip().dxy();
+            this.front[d as usize] = shift(atoms, back);
+            this.front_left[d as usize] = shift(shift(atoms, back), d.clockwise().dxy());
+            this.front_right[d as usize] = shift(shift(atoms, back), d.counter_clockwise().dxy());
+            this.events[d as usize] =
+                this.front[d as usize] | this.front_left[d as usize] | this.front_right[d as usize];
+        }
+        this
+    }
+
+    fn has_atom(&self, v: I8Vec2) -> bool {
+        v.in_grid() && self.atoms & bit(v) != 0
+    }
+
+    /// Shines a laser in from the border, like [crate::observation::shoot].
+    pub fn shoot(&self, shift: u8, direction: Direction, ruleset: Ruleset) -> ProbeResult {
+        let entry = LaserTip::new(shift, direction).position();
+        let mut position = entry + direction.dxy();
+        let mut direction = direction;
+
+        // Entering the box follows the edge rules, see LaserTip::traverse_grid.
+        if self.has_atom(position) {
+            return ProbeResult::Absorbed;
+        }
+        let left = self.has_atom(position + direction.counter_clockwise().dxy());
+        let right = self.has_atom(position + direction.clockwise().dxy());
+        match (left, right, ruleset) {
+            (false, false, _) => {}
+            (true, true, _) | (_, _, Ruleset::Classic) => return ProbeResult::Reflected,
+            (_, _, Ruleset::Plain) => return ProbeResult::Absorbed,
+        }
+
+        for _ in 0..MAX_MOVES {
+            let d = direction as usize;
+            let ahead = RAYS[d][index(position)] & self.events[d];
+            if ahead == 0 {
+                // Nothing in the way, leave the grid on the opposite side.
+                let last = GRID_SIZE as i8;
+                let position = match direction {
+                    Direction::Up => I8Vec2::new(position.x, -1),
+                    Direction::Down => I8Vec2::new(position.x, last),
+                    Direction::Left => I8Vec2::new(-1, position.y),
+                    Direction::Right => I8Vec2::new(last, position.y),
+                };
+                return exit(entry, position);
+            }
+            // The closest event is the lowest bit when moving to higher indices.
+            let i = match direction {
+                Direction::Down | Direction::Right => ahead.trailing_zeros() as usize,
+                Direction::Up | Direction::Left => 63 - ahead.leading_zeros() as usize,
+            };
+            position = I8Vec2::new((i % GRID_SIZE) as i8, (i / GRID_SIZE) as i8);
+            let b = 1 << i;
+            if self.front[d] & b != 0 {
+                return ProbeResult::Absorbed;
+            }
+            let left = self.front_left[d] & b != 0;
+            let right = self.front_right[d] & b != 0;
+            (position, direction) = match (left, right) {
+                (false, false) => unreachable!("The laser only stops where something happens."),
+                (true, true) => (position - direction.dxy(), direction.flip()),
+                (true, false) => (
+                    position + direction.clockwise().dxy(),
+                    direction.clockwise(),
+                ),
+                (false, true) => (
+                    position + direction.counter_clockwise().dxy(),
+                    direction.counter_clockwise(),
+                ),
+            };
+            if !position.in_grid() {
+                return exit(entry, position);
+            }
+        }
+        // Running in circles is observed like an absorption.
+        ProbeResult::Absorbed
+    }
+}
+
+/// Turns the position of a laser which just left the grid into the observation.
+fn exit(entry: I8Vec2, position: I8Vec2) -> ProbeResult {
+    if position == entry {
+        return ProbeResult::Reflected;
+    }
+    let last = GRID_SIZE as i8;
+    let (shift, direction) = if position.x == -1 {
+        (position.y, Direction::Right)
+    } else if position.x == last {
+        (position.y, Direction::Left)
+    } else if position.y == -1 {
+        (position.x, Direction::Down)
+    } else {
+        (position.x, Direction::Up)
+    };
+    ProbeResult::Exit(shift as u8, direction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observation::{self, Observations};
+
+    #[test]
+    fn same_results_as_the_laser() {
+        for ruleset in [Ruleset::Classic, Ruleset::Plain] {
+            for atom_count in [1, 3, 5, 8, 12] {
+                for _ in 0..50 {
+                    let grid = AtomGrid::random(atom_count);
+                    let probe = BitboardProbe::new(&grid);
+                    for direction in Direction::all() {
+                        for shift in 0..GRID_SIZE as u8 {
+                            let laser = LaserTip::new(shift, direction);
+                            assert_eq!(
+                                probe.shoot(shift, direction, ruleset),
+                                observation::shoot(laser, &grid, ruleset),
+                                "{} from {:?} {}",
+                                grid.as_bitboard(),
+                                direction,
+                                shift
+                            );
+                        }
+                    }
+                    assert_eq!(
+                        Observations::observe_all_fast(&grid, ruleset),
+                        Observations::observe_all(&grid, ruleset)
+                    );
+                }
+            }
+        }
+    }
+}
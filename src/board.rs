@@ -0,0 +1,240 @@
+//! The player's notes: marks on the cells and notes on the sides of the box. Unlike an
+//! [UncertainGrid](crate::solver::UncertainGrid), which only holds what follows from the
+//! observations, the board holds whatever the player thinks, right or wrong. So it can contradict
+//! the observations, and [Board::conflicts] tells where.
+
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::i8vec2::I8Vec2;
+use crate::laser::{side_name, Direction, LaserTip, TraversalOutcome};
+use crate::observation::{
+    self, Observation, Observations, ProbeResult, LASER_ABSORBED, LASER_REFLECTED, NOT_PROBED,
+};
+use std::fmt::{Display, Formatter};
+
+/// What the player thinks is in a cell.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Mark {
+    /// There is an atom.
+    Atom,
+    /// There is no atom.
+    Empty,
+    /// There might be an atom. Counts as undecided when checking the board.
+    Maybe,
+}
+
+/// The marks and notes of one player.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Board {
+    marks: [[Option<Mark>; GRID_SIZE]; GRID_SIZE],
+    notes: [[Option<String>; GRID_SIZE]; 4],
+}
+
+impl Board {
+    /// The mark on a cell, which must be in the box.
+    pub fn mark(&self, cell: I8Vec2) -> Option<Mark> {
+        self.marks[cell.x as usize][cell.y as usize]
+    }
+
+    /// Marks a cell, which must be in the box, or clears it with `None`.
+    pub fn set_mark(&mut self, cell: I8Vec2, mark: Option<Mark>) {
+        self.marks[cell.x as usize][cell.y as usize] = mark;
+    }
+
+    /// The note at a position on the border, given as the parameters of [LaserTip::new].
+    pub fn note(&self, shift: u8, direction: Direction) -> Option<&str> {
+        self.notes[direction as usize][shift as usize].as_deref()
+    }
+
+    /// Writes a note at a position on the border, or clears it with `None`.
+    pub fn set_note(&mut self, shift: u8, direction: Direction, note: Option<String>) {
+        self.notes[direction as usize][shift as usize] = note;
+    }
+
+    /// The cells marked as atoms, e.g. to guess them.
+    pub fn atoms(&self) -> AtomGrid {
+        let mut grid = AtomGrid::default();
+        for x in 0..GRID_SIZE {
+            for y in 0..GRID_SIZE {
+                if self.marks[x][y] == Some(Mark::Atom) {
+                    grid.set(I8Vec2::new(x as i8, y as i8), true);
+                }
+            }
+        }
+        grid
+    }
+
+    /// Shines the lasers of all probed positions through the cells marked as atoms and lists the
+    /// ones which don't give what was observed. Every position is checked, so both ends of a
+    /// letter can show up.
+    pub fn conflicts(&self, observations: &Observations) -> Vec<Conflict> {
+        let atoms = self.atoms();
+        let mut conflicts = vec![];
+        for (direction, shift, observed) in observations.iter() {
+            if observed == NOT_PROBED {
+                continue;
+            }
+            let laser = LaserTip::new(shift, direction);
+            let simulated = observation::shoot(laser, &atoms, observations.ruleset);
+            let matches = match simulated {
+                ProbeResult::Absorbed => observed == LASER_ABSORBED,
+                ProbeResult::Reflected => observed == LASER_REFLECTED,
+                ProbeResult::Exit(out_shift, out_direction) => {
+                    observed.is_letter()
+                        && observations.sides[out_direction as usize][out_shift as usize]
+                            == observed
+                }
+            };
+            if !matches {
+                conflicts.push(Conflict {
+                    shift,
+                    direction,
+                    observed,
+                    simulated,
+                    certain: self.decides(laser, observations),
+                });
+            }
+        }
+        conflicts
+    }
+
+    /// Whether the laser only looks at cells marked as atom or empty on its way, so no other
+    /// atoms could change where it goes.
+    fn decides(&self, laser: LaserTip, observations: &Observations) -> bool {
+        let path = laser.record_path(&self.atoms(), observations.ruleset);
+        let decided = |v: I8Vec2| {
+            !v.in_grid() || matches!(self.mark(v), Some(Mark::Atom) | Some(Mark::Empty))
+        };
+        // Every move looks at the cell in front and, unless that one holds an atom, the two
+        // cells on its corners.
+        let (last, moved) = path.tips.split_last().expect("A path has a start.");
+        let looked_at = moved.iter().all(|tip| {
+            let front = tip.position() + tip.direction().dxy();
+            decided(front)
+                && decided(front + tip.direction().clockwise().dxy())
+                && decided(front + tip.direction().counter_clockwise().dxy())
+        });
+        let absorbed_by_decided = match path.outcome {
+            TraversalOutcome::Absorbed { .. } => decided(last.position() + last.direction().dxy()),
+            _ => true,
+        };
+        looked_at && absorbed_by_decided
+    }
+}
+
+/// A probed position where the marked atoms don't give what was observed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Conflict {
+    /// The row or column of the position, as for [LaserTip::new].
+    pub shift: u8,
+    /// The direction of the laser shone in from the position.
+    pub direction: Direction,
+    /// What was seen there.
+    pub observed: Observation,
+    /// What the laser does with just the marked atoms in the box.
+    pub simulated: ProbeResult,
+    /// True if the laser only passes cells marked as atom or empty. Otherwise more atoms in the
+    /// other cells could still explain the observation.
+    pub certain: bool,
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at {} {}: your atoms would ",
+            self.observed,
+            side_name(self.direction),
+            self.shift
+        )?;
+        match self.simulated {
+            ProbeResult::Absorbed => write!(f, "absorb the laser")?,
+            ProbeResult::Reflected => write!(f, "reflect the laser")?,
+            ProbeResult::Exit(shift, direction) => write!(
+                f,
+                "make the laser come out at {} {}",
+                side_name(direction),
+                shift
+            )?,
+        }
+        if !self.certain {
+            write!(f, ", unless there are atoms in cells you haven't marked")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laser::Direction::*;
+    use crate::laser::Ruleset;
+
+    fn board(atoms: &[(i8, i8)], empty: &[(i8, i8)]) -> Board {
+        let mut board = Board::default();
+        for &(x, y) in atoms {
+            board.set_mark(I8Vec2::new(x, y), Some(Mark::Atom));
+        }
+        for &(x, y) in empty {
+            board.set_mark(I8Vec2::new(x, y), Some(Mark::Empty));
+        }
+        board
+    }
+
+    #[test]
+    fn right_marks_have_no_conflicts() {
+        let grid = AtomGrid::from_bitboard(35184640598018);
+        let observations = Observations::observe_all(&grid, Ruleset::Classic);
+        let atoms: Vec<(i8, i8)> = grid.atoms().map(|v| (v.x, v.y)).collect();
+        let mut board = board(&atoms, &[]);
+        board.set_mark(I8Vec2::new(7, 7), Some(Mark::Maybe));
+        assert!(board.conflicts(&observations).is_empty());
+        assert_eq!(board.atoms(), grid);
+    }
+
+    #[test]
+    fn wrong_marks_conflict() {
+        // A single atom at (3, 3), the player put it at (3, 4).
+        let mut grid = AtomGrid::default();
+        grid.set(I8Vec2::new(3, 3), true);
+        let mut observations = Observations::new(Ruleset::Classic);
+        observations.probe(LaserTip::new(3, Down), &grid);
+
+        // The laser from the top is absorbed by either atom.
+        let guess = board(&[(3, 4)], &[]);
+        assert!(guess.conflicts(&observations).is_empty());
+
+        // From the left in row 3 the guess turns the laser up and out at the top.
+        observations.probe(LaserTip::new(3, Right), &grid);
+        let conflicts = guess.conflicts(&observations);
+        assert_eq!(conflicts.len(), 1);
+        let conflict = conflicts[0];
+        assert_eq!((conflict.shift, conflict.direction), (3, Right));
+        assert_eq!(conflict.observed, LASER_ABSORBED);
+        assert_eq!(conflict.simulated, ProbeResult::Exit(2, Down));
+        assert!(!conflict.certain);
+        assert_eq!(
+            conflict.to_string(),
+            "× at left 3: your atoms would make the laser come out at top 2, \
+             unless there are atoms in cells you haven't marked"
+        );
+
+        // With every other cell marked empty, no atom could help.
+        let empty: Vec<(i8, i8)> = (0..GRID_SIZE as i8)
+            .flat_map(|x| (0..GRID_SIZE as i8).map(move |y| (x, y)))
+            .filter(|&cell| cell != (3, 4))
+            .collect();
+        let conflicts = board(&[(3, 4)], &empty).conflicts(&observations);
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0].certain);
+    }
+
+    #[test]
+    fn notes_on_the_border() {
+        let mut board = Board::default();
+        board.set_note(2, Left, Some("checked".to_owned()));
+        assert_eq!(board.note(2, Left), Some("checked"));
+        assert_eq!(board.note(2, Right), None);
+        board.set_note(2, Left, None);
+        assert_eq!(board, Board::default());
+    }
+}
@@ -0,0 +1,125 @@
+//! A printable booklet: a set of puzzles with unique solutions, from easy to hard, and the
+//! solutions in the back. It is a single HTML file, print it from the browser.
+
+use crate::atom_grid::AtomGrid;
+use crate::generator::{self, Difficulty};
+use crate::laser::Ruleset;
+use crate::observation;
+use crate::puzzle::Puzzle;
+use crate::solver::{self, UncertainGrid};
+use crate::svg::escape;
+use rand::Rng;
+use std::fmt::Write;
+
+/// A puzzle in the booklet.
+pub struct Entry {
+    /// The solution.
+    pub grid: AtomGrid,
+    /// What is printed on the front pages.
+    pub puzzle: Puzzle,
+    /// Always [Difficulty::of] the puzzle, the generator may not have found the one it was after.
+    pub difficulty: Difficulty,
+}
+
+/// Generates `count` puzzles, taking turns between the difficulties. A difficulty which can't be
+/// found with this many atoms is replaced by whatever comes up. The result is sorted from easy to
+/// hard.
+pub fn generate<R: Rng>(count: usize, atom_count: u8, ruleset: Ruleset, rng: &mut R) -> Vec<Entry> {
+    let difficulties = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+    let mut entries: Vec<Entry> = (0..count)
+        .filter_map(|i| {
+            let wanted = Some(difficulties[i % difficulties.len()]);
+            let (grid, puzzle) = generator::generate(atom_count, ruleset, wanted, rng)
+                .or_else(|| generator::generate(atom_count, ruleset, None, rng))?;
+            let difficulty = Difficulty::of(&puzzle);
+            Some(Entry {
+                grid,
+                puzzle,
+                difficulty,
+            })
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.difficulty);
+    entries
+}
+
+const STYLE: &str = "\
+body { font-family: sans-serif; }
+h1 { text-align: center; }
+.puzzle { break-inside: avoid; display: inline-block; margin: 1em 2em; }
+.solutions { break-before: page; }
+pre { font-size: 14pt; line-height: 1.3; }
+";
+
+/// The booklet as an HTML document. The grids are the text drawings of [observation::draw].
+pub fn html(title: &str, entries: &[Entry]) -> Result<String, std::fmt::Error> {
+    let mut f = String::new();
+    writeln!(f, "<!DOCTYPE html>")?;
+    writeln!(f, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(f, "<title>{}</title>", escape(title))?;
+    writeln!(f, "<style>\n{}</style>", STYLE)?;
+    writeln!(f, "</head><body>")?;
+    writeln!(f, "<h1>{}</h1>", escape(title))?;
+
+    writeln!(f, "<section class=\"puzzles\">")?;
+    for (i, entry) in entries.iter().enumerate() {
+        let drawing = solver::draw(&UncertainGrid::default(), &entry.puzzle.observations)?;
+        writeln!(
+            f,
+            "<div class=\"puzzle\"><h2>Puzzle {}</h2><p>{} atoms, {}{}</p><pre>{}</pre></div>",
+            i + 1,
+            entry.puzzle.atom_count,
+            entry.difficulty,
+            rules_note(entry.puzzle.observations.ruleset),
+            escape(&drawing)
+        )?;
+    }
+    writeln!(f, "</section>")?;
+
+    writeln!(f, "<section class=\"solutions\"><h1>Solutions</h1>")?;
+    for (i, entry) in entries.iter().enumerate() {
+        let drawing = observation::draw(&entry.grid, &entry.puzzle.observations)?;
+        writeln!(
+            f,
+            "<div class=\"puzzle\"><h2>Solution {}</h2><pre>{}</pre></div>",
+            i + 1,
+            escape(&drawing)
+        )?;
+    }
+    writeln!(f, "</section>")?;
+    writeln!(f, "</body></html>")?;
+    Ok(f)
+}
+
+fn rules_note(ruleset: Ruleset) -> String {
+    match ruleset {
+        Ruleset::Classic => String::new(),
+        ruleset => format!(", {} rules", ruleset),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn puzzles_in_front_and_solutions_in_the_back() {
+        let entries = generate(3, 4, Ruleset::Classic, &mut StdRng::seed_from_u64(1));
+        assert_eq!(entries.len(), 3);
+        assert!(entries
+            .windows(2)
+            .all(|w| w[0].difficulty <= w[1].difficulty));
+
+        let html = html("Laser <Puzzles>", &entries).unwrap();
+        assert!(html.contains("<title>Laser &lt;Puzzles&gt;</title>"));
+        assert_eq!(html.matches("<h2>Puzzle").count(), 3);
+        assert_eq!(html.matches("<h2>Solution").count(), 3);
+        assert!(html.find("<h2>Puzzle 3").unwrap() < html.find("<h2>Solution 1").unwrap());
+        // Only the solutions show atoms.
+        let solutions = html.find("class=\"solutions\"").unwrap();
+        assert!(!html[..solutions].contains(" o "));
+        assert!(html[solutions..].contains(" o "));
+    }
+}
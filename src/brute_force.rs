@@ -0,0 +1,85 @@
+//! Finds atom grids matching a puzzle by trying all placements of atoms. The solver rules are used
+//! first to rule out as many cells as possible, which keeps the search small in practice.
+
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::i8vec2::I8Vec2;
+use crate::puzzle::Puzzle;
+use crate::solver;
+
+/// Finds up to `limit` grids which have the right amount of atoms and explain all observations.
+pub fn find_solutions(puzzle: &Puzzle, limit: usize) -> Vec<AtomGrid> {
+    let knowledge = solver::solve_as_much_as_you_can(&puzzle.observations);
+    let candidates: Vec<I8Vec2> = (0..GRID_SIZE)
+        .flat_map(|y| (0..GRID_SIZE).map(move |x| I8Vec2::new(x as i8, y as i8)))
+        .filter(|&v| !knowledge.is_empty(v))
+        .collect();
+
+    let mut solutions = vec![];
+    let mut grid = AtomGrid::default();
+    place_atoms(
+        puzzle,
+        &candidates,
+        puzzle.atom_count as usize,
+        &mut grid,
+        &mut solutions,
+        limit,
+    );
+    solutions
+}
+
+/// A puzzle is unique, if exactly one grid explains it.
+pub fn is_unique(puzzle: &Puzzle) -> bool {
+    find_solutions(puzzle, 2).len() == 1
+}
+
+fn place_atoms(
+    puzzle: &Puzzle,
+    candidates: &[I8Vec2],
+    remaining: usize,
+    grid: &mut AtomGrid,
+    solutions: &mut Vec<AtomGrid>,
+    limit: usize,
+) {
+    if solutions.len() >= limit {
+        return;
+    }
+    if remaining == 0 {
+        if puzzle.observations.is_explained_by(grid) {
+            solutions.push(*grid);
+        }
+        return;
+    }
+    for (i, &v) in candidates.iter().enumerate() {
+        if candidates.len() - i < remaining {
+            return;
+        }
+        grid.set(v, true);
+        place_atoms(
+            puzzle,
+            &candidates[i + 1..],
+            remaining - 1,
+            grid,
+            solutions,
+            limit,
+        );
+        grid.set(v, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laser::Ruleset;
+
+    #[test]
+    fn hidden_grid_is_always_found() {
+        for ruleset in [Ruleset::Classic, Ruleset::Plain] {
+            for _ in 0..10 {
+                let grid = AtomGrid::random(3);
+                let puzzle = Puzzle::from_grid(&grid, 3, ruleset);
+                let solutions = find_solutions(&puzzle, usize::MAX);
+                assert!(solutions.contains(&grid), "Missing {}", grid.as_bitboard());
+            }
+        }
+    }
+}
@@ -0,0 +1,502 @@
+//! A C interface, built with `--features capi`. The header `include/laser_puzzle.h` is generated
+//! from this file by cbindgen during the build.
+//!
+//! Grids and observations are opaque handles, created by the `lp_*_new` style functions and
+//! released with [lp_grid_free] and [lp_observations_free]. Sides of the box are named like on the
+//! command line. Functions return `false` or `NULL` for arguments out of range, e.g. a shift of 8.
+//! A panic never unwinds into the caller, it is caught and reported the same way.
+//!
+//! # Safety
+//!
+//! Handles must come from this library and not be used after they are freed. Pointers to results
+//! must point to enough writable memory. Enum arguments must be one of the declared values.
+
+// The rules are the same for every function, see above.
+#![allow(clippy::missing_safety_doc)]
+
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::brute_force;
+use crate::generator::{self, Difficulty};
+use crate::i8vec2::I8Vec2;
+use crate::laser::Direction::{self, Down, Left, Right, Up};
+use crate::laser::{LaserTip, Ruleset};
+use crate::observation::{self, Observations, ProbeResult};
+use crate::puzzle::Puzzle;
+use crate::solver::{self, GridKnowledge};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// The atoms hidden in the box.
+pub struct LpGrid(AtomGrid);
+
+/// What the player sees on the sides of the box.
+pub struct LpObservations(Observations);
+
+/// A side of the box, where a laser is shone in or comes out.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LpSide {
+    /// The top side, a laser shone in from here moves down.
+    Top,
+    /// The bottom side.
+    Bottom,
+    /// The left side.
+    Left,
+    /// The right side.
+    Right,
+}
+
+impl LpSide {
+    fn entry_direction(self) -> Direction {
+        match self {
+            LpSide::Top => Down,
+            LpSide::Bottom => Up,
+            LpSide::Left => Right,
+            LpSide::Right => Left,
+        }
+    }
+
+    fn of_entry_direction(direction: Direction) -> Self {
+        match direction {
+            Down => LpSide::Top,
+            Up => LpSide::Bottom,
+            Right => LpSide::Left,
+            Left => LpSide::Right,
+        }
+    }
+}
+
+/// The variant of the laser rules, see [Ruleset].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LpRules {
+    /// A laser entering next to an atom on the edge is reflected.
+    Classic,
+    /// A single atom next to the entry turns the laser away, which looks like an absorption.
+    Plain,
+}
+
+impl From<LpRules> for Ruleset {
+    fn from(rules: LpRules) -> Self {
+        match rules {
+            LpRules::Classic => Ruleset::Classic,
+            LpRules::Plain => Ruleset::Plain,
+        }
+    }
+}
+
+/// The difficulty of generated puzzles, see [Difficulty].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LpDifficulty {
+    /// Whatever comes up first.
+    Any,
+    /// See [Difficulty::Easy].
+    Easy,
+    /// See [Difficulty::Medium].
+    Medium,
+    /// See [Difficulty::Hard].
+    Hard,
+}
+
+/// How a probe ended.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LpOutcome {
+    /// The laser never came out.
+    Absorbed,
+    /// The laser came out where it went in.
+    Reflected,
+    /// The laser came out somewhere else.
+    Exit,
+}
+
+/// The result of shining a laser into the box.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LpProbe {
+    /// How the probe ended.
+    pub outcome: LpOutcome,
+    /// Where the laser came out, or where it went in if it didn't.
+    pub side: LpSide,
+    /// Row or column on that side, counted from the top left.
+    pub shift: u8,
+}
+
+/// What is known about a cell, written by [lp_solve].
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LpKnowledge {
+    /// Could be an atom or empty.
+    Unknown,
+    /// Holds an atom.
+    Atom,
+    /// Is empty.
+    Empty,
+}
+
+/// Runs the body of an exported function and returns `failed` if it panics, as unwinding into C
+/// would abort the whole program.
+fn catch<T>(failed: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(failed)
+}
+
+fn handle<T>(value: T) -> *mut T {
+    Box::into_raw(Box::new(value))
+}
+
+fn cell(x: i32, y: i32) -> Option<I8Vec2> {
+    let size = GRID_SIZE as i32;
+    ((0..size).contains(&x) && (0..size).contains(&y)).then(|| I8Vec2::new(x as i8, y as i8))
+}
+
+fn laser(side: LpSide, shift: u8) -> Option<LaserTip> {
+    ((shift as usize) < GRID_SIZE).then(|| LaserTip::new(shift, side.entry_direction()))
+}
+
+/// An empty box.
+#[no_mangle]
+pub extern "C" fn lp_grid_new() -> *mut LpGrid {
+    handle(LpGrid(AtomGrid::default()))
+}
+
+/// A box with atoms in random cells, the same ones for the same seed. `NULL` for more atoms than
+/// cells.
+#[no_mangle]
+pub extern "C" fn lp_grid_random(atoms: u8, seed: u64) -> *mut LpGrid {
+    if atoms as usize > GRID_SIZE * GRID_SIZE {
+        return ptr::null_mut();
+    }
+    catch(ptr::null_mut(), || {
+        let grid = AtomGrid::random_with(atoms, &mut StdRng::seed_from_u64(seed));
+        handle(LpGrid(grid))
+    })
+}
+
+/// A box from a number as printed by the command line, see [AtomGrid::as_bitboard].
+#[no_mangle]
+pub extern "C" fn lp_grid_from_bitboard(bitboard: u64) -> *mut LpGrid {
+    handle(LpGrid(AtomGrid::from_bitboard(bitboard)))
+}
+
+/// Releases a grid. Does nothing for `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn lp_grid_free(grid: *mut LpGrid) {
+    if !grid.is_null() {
+        drop(Box::from_raw(grid));
+    }
+}
+
+/// The grid as a number, see [AtomGrid::as_bitboard].
+#[no_mangle]
+pub unsafe extern "C" fn lp_grid_bitboard(grid: *const LpGrid) -> u64 {
+    (*grid).0.as_bitboard()
+}
+
+/// Whether there is an atom in column `x` and row `y`, counted from the top left.
+#[no_mangle]
+pub unsafe extern "C" fn lp_grid_get(grid: *const LpGrid, x: i32, y: i32) -> bool {
+    cell(x, y).is_some_and(|v| (*grid).0.get(v))
+}
+
+/// Puts an atom into a cell or takes it out. Returns `false` for cells outside the box.
+#[no_mangle]
+pub unsafe extern "C" fn lp_grid_set(grid: *mut LpGrid, x: i32, y: i32, atom: bool) -> bool {
+    let Some(v) = cell(x, y) else {
+        return false;
+    };
+    (*grid).0.set(v, atom);
+    true
+}
+
+/// The number of atoms in the grid.
+#[no_mangle]
+pub unsafe extern "C" fn lp_grid_atom_count(grid: *const LpGrid) -> u32 {
+    (*grid).0.atom_count()
+}
+
+/// Shines a laser into the grid and writes what happened to `result`.
+#[no_mangle]
+pub unsafe extern "C" fn lp_probe(
+    grid: *const LpGrid,
+    rules: LpRules,
+    side: LpSide,
+    shift: u8,
+    result: *mut LpProbe,
+) -> bool {
+    let Some(laser) = laser(side, shift) else {
+        return false;
+    };
+    catch(false, || {
+        let (outcome, side, shift) = match observation::shoot(laser, &(*grid).0, rules.into()) {
+            ProbeResult::Absorbed => (LpOutcome::Absorbed, side, shift),
+            ProbeResult::Reflected => (LpOutcome::Reflected, side, shift),
+            ProbeResult::Exit(out_shift, out_direction) => (
+                LpOutcome::Exit,
+                LpSide::of_entry_direction(out_direction),
+                out_shift,
+            ),
+        };
+        *result = LpProbe {
+            outcome,
+            side,
+            shift,
+        };
+        true
+    })
+}
+
+/// Observations without any probes yet.
+#[no_mangle]
+pub extern "C" fn lp_observations_new(rules: LpRules) -> *mut LpObservations {
+    handle(LpObservations(Observations::new(rules.into())))
+}
+
+/// The observations of a laser shone in from every position on the border.
+#[no_mangle]
+pub unsafe extern "C" fn lp_observations_observe_all(
+    grid: *const LpGrid,
+    rules: LpRules,
+) -> *mut LpObservations {
+    catch(ptr::null_mut(), || {
+        handle(LpObservations(Observations::observe_all(
+            &(*grid).0,
+            rules.into(),
+        )))
+    })
+}
+
+/// Releases observations. Does nothing for `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn lp_observations_free(observations: *mut LpObservations) {
+    if !observations.is_null() {
+        drop(Box::from_raw(observations));
+    }
+}
+
+/// Shines a laser into the grid and records the result on both sides where it went in and out.
+/// Probing a position which already shows a result changes nothing.
+#[no_mangle]
+pub unsafe extern "C" fn lp_observations_probe(
+    observations: *mut LpObservations,
+    grid: *const LpGrid,
+    side: LpSide,
+    shift: u8,
+) -> bool {
+    let Some(laser) = laser(side, shift) else {
+        return false;
+    };
+    catch(false, || {
+        (*observations).0.probe(laser, &(*grid).0);
+        true
+    })
+}
+
+/// The symbol on the side as a Unicode code point: `?` if not probed yet, `×` (U+00D7) for an
+/// absorption, `⇄` (U+21C4) for a reflection, or a capital letter which appears on the sides
+/// twice. Returns 0 for a shift out of range.
+#[no_mangle]
+pub unsafe extern "C" fn lp_observations_get(
+    observations: *const LpObservations,
+    side: LpSide,
+    shift: u8,
+) -> u32 {
+    if shift as usize >= GRID_SIZE {
+        return 0;
+    }
+    catch(0, || {
+        let obs = (*observations).0.sides[side.entry_direction() as usize][shift as usize];
+        obs.to_string().chars().next().map_or(0, u32::from)
+    })
+}
+
+/// Writes what the rule based solver finds out to `knowledge`, 64 cells row by row from the top
+/// left. `knowledge` must have room for 64 values. Returns `false` if the solver failed, then
+/// `knowledge` is left unchanged.
+#[no_mangle]
+pub unsafe extern "C" fn lp_solve(
+    observations: *const LpObservations,
+    knowledge: *mut LpKnowledge,
+) -> bool {
+    catch(false, || {
+        let grid = solver::solve_as_much_as_you_can(&(*observations).0);
+        let knowledge = std::slice::from_raw_parts_mut(knowledge, GRID_SIZE * GRID_SIZE);
+        for (i, cell) in knowledge.iter_mut().enumerate() {
+            let v = I8Vec2::new((i % GRID_SIZE) as i8, (i / GRID_SIZE) as i8);
+            *cell = match grid.get(v) {
+                GridKnowledge::Unknown => LpKnowledge::Unknown,
+                GridKnowledge::Atom => LpKnowledge::Atom,
+                GridKnowledge::Empty => LpKnowledge::Empty,
+            };
+        }
+        true
+    })
+}
+
+/// Finds up to `capacity` grids with `atoms` atoms which explain the observations, and writes
+/// them to `solutions` as bitboards. Returns how many were found; ask for two to learn whether
+/// the solution is unique. `solutions` must have room for `capacity` values. Returns 0 if the
+/// search failed.
+#[no_mangle]
+pub unsafe extern "C" fn lp_find_solutions(
+    observations: *const LpObservations,
+    atoms: u8,
+    solutions: *mut u64,
+    capacity: usize,
+) -> usize {
+    catch(0, || {
+        let puzzle = Puzzle {
+            atom_count: atoms,
+            observations: (*observations).0.clone(),
+        };
+        let found = brute_force::find_solutions(&puzzle, capacity);
+        let solutions = std::slice::from_raw_parts_mut(solutions, capacity);
+        for (solution, grid) in solutions.iter_mut().zip(&found) {
+            *solution = grid.as_bitboard();
+        }
+        found.len()
+    })
+}
+
+/// A random grid whose fully probed puzzle has exactly one solution, the same one for the same
+/// seed. Probe it with [lp_observations_observe_all] to get the puzzle. Returns `NULL` if none was
+/// found, e.g. for too many atoms.
+#[no_mangle]
+pub extern "C" fn lp_generate(
+    atoms: u8,
+    rules: LpRules,
+    difficulty: LpDifficulty,
+    seed: u64,
+) -> *mut LpGrid {
+    let difficulty = match difficulty {
+        LpDifficulty::Any => None,
+        LpDifficulty::Easy => Some(Difficulty::Easy),
+        LpDifficulty::Medium => Some(Difficulty::Medium),
+        LpDifficulty::Hard => Some(Difficulty::Hard),
+    };
+    catch(ptr::null_mut(), || {
+        let mut rng = StdRng::seed_from_u64(seed);
+        match generator::generate(atoms, rules.into(), difficulty, &mut rng) {
+            Some((grid, _)) => handle(LpGrid(grid)),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_probe_and_solve() {
+        unsafe {
+            let grid = lp_generate(4, LpRules::Classic, LpDifficulty::Any, 3);
+            assert!(!grid.is_null());
+            assert_eq!(lp_grid_atom_count(grid), 4);
+
+            let observations = lp_observations_observe_all(grid, LpRules::Classic);
+            let mut solutions = [0u64; 2];
+            assert_eq!(
+                lp_find_solutions(observations, 4, solutions.as_mut_ptr(), 2),
+                1
+            );
+            assert_eq!(solutions[0], lp_grid_bitboard(grid));
+
+            let mut knowledge = [LpKnowledge::Unknown; 64];
+            assert!(lp_solve(observations, knowledge.as_mut_ptr()));
+            for (i, k) in knowledge.iter().enumerate() {
+                let (x, y) = ((i % 8) as i32, (i / 8) as i32);
+                if *k == LpKnowledge::Empty {
+                    assert!(!lp_grid_get(grid, x, y));
+                }
+            }
+
+            lp_observations_free(observations);
+            lp_grid_free(grid);
+        }
+    }
+
+    #[test]
+    fn probes_are_recorded_on_both_sides() {
+        unsafe {
+            let grid = lp_grid_new();
+            assert!(lp_grid_set(grid, 3, 4, true));
+            assert!(!lp_grid_set(grid, 8, 0, true));
+
+            let mut probe = LpProbe {
+                outcome: LpOutcome::Absorbed,
+                side: LpSide::Top,
+                shift: 0,
+            };
+            assert!(lp_probe(
+                grid,
+                LpRules::Classic,
+                LpSide::Left,
+                0,
+                &mut probe
+            ));
+            assert_eq!(
+                probe,
+                LpProbe {
+                    outcome: LpOutcome::Exit,
+                    side: LpSide::Right,
+                    shift: 0
+                }
+            );
+            assert!(lp_probe(grid, LpRules::Classic, LpSide::Top, 3, &mut probe));
+            assert_eq!(probe.outcome, LpOutcome::Absorbed);
+            assert!(!lp_probe(
+                grid,
+                LpRules::Classic,
+                LpSide::Top,
+                8,
+                &mut probe
+            ));
+
+            let observations = lp_observations_new(LpRules::Classic);
+            assert_eq!(
+                lp_observations_get(observations, LpSide::Left, 0),
+                '?' as u32
+            );
+            assert!(lp_observations_probe(observations, grid, LpSide::Left, 0));
+            assert!(lp_observations_probe(observations, grid, LpSide::Top, 3));
+            assert_eq!(
+                lp_observations_get(observations, LpSide::Left, 0),
+                'A' as u32
+            );
+            assert_eq!(
+                lp_observations_get(observations, LpSide::Right, 0),
+                'A' as u32
+            );
+            assert_eq!(
+                lp_observations_get(observations, LpSide::Top, 3),
+                '×' as u32
+            );
+            assert_eq!(lp_observations_get(observations, LpSide::Top, 9), 0);
+
+            // Probing again neither uses up letters nor changes the sides.
+            for _ in 0..30 {
+                assert!(lp_observations_probe(observations, grid, LpSide::Right, 0));
+            }
+            assert_eq!(
+                lp_observations_get(observations, LpSide::Left, 0),
+                'A' as u32
+            );
+            assert_eq!(
+                lp_observations_get(observations, LpSide::Right, 0),
+                'A' as u32
+            );
+
+            lp_observations_free(observations);
+            lp_grid_free(grid);
+            lp_grid_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn panics_dont_reach_the_caller() {
+        assert!(lp_generate(65, LpRules::Classic, LpDifficulty::Any, 1).is_null());
+    }
+}
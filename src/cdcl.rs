@@ -0,0 +1,514 @@
+//! A small conflict driven clause learning SAT solver. It has what makes such solvers fast in
+//! practice: two watched literals per clause, learning the first unique implication point, VSIDS
+//! variable activities with phase saving and Luby restarts. Learnt clauses are never deleted, which
+//! is fine for the size of problems we throw at it.
+//!
+//! Clauses can be added between calls to [Solver::solve], e.g. to block a solution and look for
+//! another one.
+
+use std::ops::Not;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Var(u32);
+
+impl Var {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A variable or its negation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Lit(u32);
+
+impl Lit {
+    pub fn positive(var: Var) -> Self {
+        Lit(var.0 << 1)
+    }
+
+    pub fn negative(var: Var) -> Self {
+        Lit(var.0 << 1 | 1)
+    }
+
+    /// The literal which is true if the variable has the given value.
+    pub fn new(var: Var, value: bool) -> Self {
+        if value {
+            Lit::positive(var)
+        } else {
+            Lit::negative(var)
+        }
+    }
+
+    pub fn var(self) -> Var {
+        Var(self.0 >> 1)
+    }
+
+    fn is_negative(self) -> bool {
+        self.0 & 1 == 1
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+const UNDEF: i8 = 0;
+const TRUE: i8 = 1;
+const FALSE: i8 = -1;
+
+/// Value of a literal under the assignment of the variables.
+fn value(assigns: &[i8], lit: Lit) -> i8 {
+    let v = assigns[lit.var().index()];
+    if lit.is_negative() {
+        -v
+    } else {
+        v
+    }
+}
+
+#[derive(Default)]
+pub struct Solver {
+    clauses: Vec<Vec<Lit>>,
+    /// For each literal, the clauses watching it. The watched literals are the first two ones.
+    watches: Vec<Vec<usize>>,
+    assigns: Vec<i8>,
+    level: Vec<usize>,
+    reason: Vec<Option<usize>>,
+    /// The last value of each variable, used again when deciding on it.
+    polarity: Vec<bool>,
+    activity: Vec<f64>,
+    activity_increment: f64,
+    order: VarHeap,
+    trail: Vec<Lit>,
+    /// Where each decision level starts on the trail.
+    trail_limits: Vec<usize>,
+    /// Literals on the trail before this index are propagated.
+    propagated: usize,
+    seen: Vec<bool>,
+    /// False once the clauses are known to be unsatisfiable.
+    unsatisfiable: bool,
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Solver {
+            activity_increment: 1.0,
+            ..Default::default()
+        }
+    }
+
+    pub fn new_var(&mut self) -> Var {
+        let var = Var(self.assigns.len() as u32);
+        self.watches.push(vec![]);
+        self.watches.push(vec![]);
+        self.assigns.push(UNDEF);
+        self.level.push(0);
+        self.reason.push(None);
+        self.polarity.push(false);
+        self.activity.push(0.0);
+        self.seen.push(false);
+        self.order.insert(var, &self.activity);
+        var
+    }
+
+    /// Adds a clause, i.e. at least one of the literals must be true.
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        self.cancel_until(0);
+        if self.unsatisfiable {
+            return;
+        }
+        let mut lits = lits.to_vec();
+        lits.sort();
+        lits.dedup();
+        if lits.windows(2).any(|w| w[0] == !w[1]) {
+            return; // Always true.
+        }
+        if lits.iter().any(|&l| value(&self.assigns, l) == TRUE) {
+            return;
+        }
+        lits.retain(|&l| value(&self.assigns, l) == UNDEF);
+        match lits.len() {
+            0 => self.unsatisfiable = true,
+            1 => {
+                self.assign(lits[0], None);
+                if self.propagate().is_some() {
+                    self.unsatisfiable = true;
+                }
+            }
+            _ => {
+                self.attach(lits);
+            }
+        }
+    }
+
+    /// Looks for an assignment which makes all clauses true. Returns the value of every variable.
+    pub fn solve(&mut self) -> Option<Vec<bool>> {
+        self.cancel_until(0);
+        if self.unsatisfiable {
+            return None;
+        }
+        let mut restarts = 0;
+        let mut conflicts = 0;
+        let mut restart_limit = 100 * luby(restarts);
+        loop {
+            if let Some(conflict) = self.propagate() {
+                if self.trail_limits.is_empty() {
+                    self.unsatisfiable = true;
+                    return None;
+                }
+                conflicts += 1;
+                let (learnt, backtrack_level) = self.analyze(conflict);
+                self.cancel_until(backtrack_level);
+                let asserting = learnt[0];
+                if learnt.len() == 1 {
+                    self.assign(asserting, None);
+                } else {
+                    let index = self.attach(learnt);
+                    self.assign(asserting, Some(index));
+                }
+                self.activity_increment /= 0.95;
+            } else {
+                if conflicts >= restart_limit {
+                    conflicts = 0;
+                    restarts += 1;
+                    restart_limit = 100 * luby(restarts);
+                    self.cancel_until(0);
+                }
+                let Some(var) = self.pick_branching_var() else {
+                    return Some(self.assigns.iter().map(|&v| v == TRUE).collect());
+                };
+                self.trail_limits.push(self.trail.len());
+                self.assign(Lit::new(var, self.polarity[var.index()]), None);
+            }
+        }
+    }
+
+    fn attach(&mut self, lits: Vec<Lit>) -> usize {
+        let index = self.clauses.len();
+        self.watches[lits[0].index()].push(index);
+        self.watches[lits[1].index()].push(index);
+        self.clauses.push(lits);
+        index
+    }
+
+    fn assign(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var().index();
+        self.assigns[var] = if lit.is_negative() { FALSE } else { TRUE };
+        self.level[var] = self.trail_limits.len();
+        self.reason[var] = reason;
+        self.trail.push(lit);
+    }
+
+    /// Assigns everything which follows from the current assignment. Returns a conflicting clause
+    /// if there is one.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.propagated < self.trail.len() {
+            let false_lit = !self.trail[self.propagated];
+            self.propagated += 1;
+            let mut watchers = std::mem::take(&mut self.watches[false_lit.index()]);
+            let mut kept = 0;
+            let mut conflict = None;
+            let mut i = 0;
+            while i < watchers.len() {
+                let index = watchers[i];
+                i += 1;
+                let clause = &mut self.clauses[index];
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                let first = clause[0];
+                if value(&self.assigns, first) == TRUE {
+                    watchers[kept] = index;
+                    kept += 1;
+                    continue;
+                }
+                if let Some(k) =
+                    (2..clause.len()).find(|&k| value(&self.assigns, clause[k]) != FALSE)
+                {
+                    clause.swap(1, k);
+                    self.watches[clause[1].index()].push(index);
+                    continue;
+                }
+                watchers[kept] = index;
+                kept += 1;
+                if value(&self.assigns, first) == FALSE {
+                    conflict = Some(index);
+                    while i < watchers.len() {
+                        watchers[kept] = watchers[i];
+                        kept += 1;
+                        i += 1;
+                    }
+                } else {
+                    self.assign(first, Some(index));
+                }
+            }
+            watchers.truncate(kept);
+            self.watches[false_lit.index()] = watchers;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    /// Learns a clause from the conflict, which is asserting after backtracking to the returned
+    /// level. The asserting literal comes first.
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        let current_level = self.trail_limits.len();
+        let mut learnt = vec![Lit(0)];
+        let mut open = 0;
+        let mut clause = conflict;
+        let mut implied: Option<Lit> = None;
+        let mut index = self.trail.len();
+        loop {
+            // The implied literal of a reason clause is its first one.
+            let skip = usize::from(implied.is_some());
+            for k in skip..self.clauses[clause].len() {
+                let lit = self.clauses[clause][k];
+                let var = lit.var().index();
+                if !self.seen[var] && self.level[var] > 0 {
+                    self.seen[var] = true;
+                    self.bump(lit.var());
+                    if self.level[var] >= current_level {
+                        open += 1;
+                    } else {
+                        learnt.push(lit);
+                    }
+                }
+            }
+            loop {
+                index -= 1;
+                if self.seen[self.trail[index].var().index()] {
+                    break;
+                }
+            }
+            let lit = self.trail[index];
+            self.seen[lit.var().index()] = false;
+            open -= 1;
+            if open == 0 {
+                learnt[0] = !lit;
+                break;
+            }
+            implied = Some(lit);
+            clause = self.reason[lit.var().index()].expect("Only decisions have no reason.");
+        }
+        for lit in &learnt[1..] {
+            self.seen[lit.var().index()] = false;
+        }
+
+        let mut backtrack_level = 0;
+        if learnt.len() > 1 {
+            let (k, _) = learnt
+                .iter()
+                .enumerate()
+                .skip(1)
+                .max_by_key(|(_, l)| self.level[l.var().index()])
+                .expect("There is a second literal.");
+            learnt.swap(1, k);
+            backtrack_level = self.level[learnt[1].var().index()];
+        }
+        (learnt, backtrack_level)
+    }
+
+    fn cancel_until(&mut self, level: usize) {
+        if self.trail_limits.len() <= level {
+            return;
+        }
+        let start = self.trail_limits[level];
+        for k in (start..self.trail.len()).rev() {
+            let lit = self.trail[k];
+            let var = lit.var();
+            self.assigns[var.index()] = UNDEF;
+            self.reason[var.index()] = None;
+            self.polarity[var.index()] = !lit.is_negative();
+            self.order.insert(var, &self.activity);
+        }
+        self.trail.truncate(start);
+        self.trail_limits.truncate(level);
+        self.propagated = start;
+    }
+
+    fn pick_branching_var(&mut self) -> Option<Var> {
+        while let Some(var) = self.order.pop(&self.activity) {
+            if self.assigns[var.index()] == UNDEF {
+                return Some(var);
+            }
+        }
+        None
+    }
+
+    fn bump(&mut self, var: Var) {
+        self.activity[var.index()] += self.activity_increment;
+        if self.activity[var.index()] > 1e100 {
+            for a in &mut self.activity {
+                *a *= 1e-100;
+            }
+            self.activity_increment *= 1e-100;
+        }
+        self.order.increased(var, &self.activity);
+    }
+}
+
+/// The Luby sequence 1, 1, 2, 1, 1, 2, 4, 1, ... used to space out restarts.
+fn luby(i: u32) -> u32 {
+    let mut i = i + 1;
+    loop {
+        let k = 32 - i.leading_zeros();
+        if i == (1 << k) - 1 {
+            return 1 << (k - 1);
+        }
+        if (1 << (k - 1)) <= i {
+            i -= (1 << (k - 1)) - 1;
+        } else {
+            return 1;
+        }
+    }
+}
+
+/// A binary max heap of variables ordered by activity.
+#[derive(Default)]
+struct VarHeap {
+    heap: Vec<Var>,
+    /// Position of each variable in the heap, if it is in there.
+    positions: Vec<Option<usize>>,
+}
+
+impl VarHeap {
+    fn insert(&mut self, var: Var, activity: &[f64]) {
+        if self.positions.len() <= var.index() {
+            self.positions.resize(var.index() + 1, None);
+        }
+        if self.positions[var.index()].is_some() {
+            return;
+        }
+        self.heap.push(var);
+        self.positions[var.index()] = Some(self.heap.len() - 1);
+        self.up(self.heap.len() - 1, activity);
+    }
+
+    fn increased(&mut self, var: Var, activity: &[f64]) {
+        if let Some(i) = self.positions[var.index()] {
+            self.up(i, activity);
+        }
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<Var> {
+        let top = *self.heap.first()?;
+        let last = self.heap.pop().expect("The heap is not empty.");
+        self.positions[top.index()] = None;
+        if !self.heap.is_empty() {
+            self.heap[0] = last;
+            self.positions[last.index()] = Some(0);
+            self.down(0, activity);
+        }
+        Some(top)
+    }
+
+    fn up(&mut self, mut i: usize, activity: &[f64]) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if activity[self.heap[parent].index()] >= activity[self.heap[i].index()] {
+                break;
+            }
+            self.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn down(&mut self, mut i: usize, activity: &[f64]) {
+        loop {
+            let mut largest = i;
+            for child in [2 * i + 1, 2 * i + 2] {
+                if child < self.heap.len()
+                    && activity[self.heap[child].index()] > activity[self.heap[largest].index()]
+                {
+                    largest = child;
+                }
+            }
+            if largest == i {
+                return;
+            }
+            self.swap(i, largest);
+            i = largest;
+        }
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        self.heap.swap(i, j);
+        self.positions[self.heap[i].index()] = Some(i);
+        self.positions[self.heap[j].index()] = Some(j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn luby_sequence() {
+        let sequence: Vec<u32> = (0..15).map(luby).collect();
+        assert_eq!(sequence, [1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+    }
+
+    /// Five pigeons don't fit into four holes, which takes some conflicts to find out.
+    #[test]
+    fn pigeonhole() {
+        let mut solver = Solver::new();
+        let (pigeons, holes) = (5, 4);
+        let sits: Vec<Vec<Var>> = (0..pigeons)
+            .map(|_| (0..holes).map(|_| solver.new_var()).collect())
+            .collect();
+        for pigeon in &sits {
+            let lits: Vec<Lit> = pigeon.iter().map(|&v| Lit::positive(v)).collect();
+            solver.add_clause(&lits);
+        }
+        // No two pigeons share a hole.
+        for a in 0..pigeons {
+            for b in a + 1..pigeons {
+                for (&x, &y) in sits[a].iter().zip(&sits[b]) {
+                    solver.add_clause(&[Lit::negative(x), Lit::negative(y)]);
+                }
+            }
+        }
+        assert_eq!(solver.solve(), None);
+    }
+
+    /// Enumerates all solutions of "exactly one of three" by blocking each one found.
+    #[test]
+    fn blocking_solutions() {
+        let mut solver = Solver::new();
+        let vars: Vec<Var> = (0..3).map(|_| solver.new_var()).collect();
+        solver.add_clause(&vars.iter().map(|&v| Lit::positive(v)).collect::<Vec<_>>());
+        for a in 0..3 {
+            for b in a + 1..3 {
+                solver.add_clause(&[Lit::negative(vars[a]), Lit::negative(vars[b])]);
+            }
+        }
+        let mut found = vec![];
+        while let Some(model) = solver.solve() {
+            let block: Vec<Lit> = vars
+                .iter()
+                .map(|&v| Lit::new(v, !model[v.index()]))
+                .collect();
+            found.push(model);
+            solver.add_clause(&block);
+        }
+        found.sort();
+        assert_eq!(
+            found,
+            [
+                vec![false, false, true],
+                vec![false, true, false],
+                vec![true, false, false]
+            ]
+        );
+    }
+}
@@ -0,0 +1,1191 @@
+//! The command line interface. Every subcommand can print plain text for humans or JSON for
+//! scripts, except `play` which is interactive.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use laser_puzzle::atom_grid::{AtomGrid, GRID_SIZE};
+use laser_puzzle::booklet;
+use laser_puzzle::brute_force;
+use laser_puzzle::enumeration::Database;
+use laser_puzzle::generator::{self, Difficulty};
+use laser_puzzle::guess::{Grading, Verdict};
+use laser_puzzle::i8vec2::I8Vec2;
+use laser_puzzle::json::{knowledge_json, puzzle_json};
+use laser_puzzle::laser::Direction::{Down, Left, Right, Up};
+use laser_puzzle::laser::{self, Direction, LaserTip, PathStep, Ruleset};
+use laser_puzzle::observation::{self, Observations, ProbeResult};
+use laser_puzzle::page;
+use laser_puzzle::puzzle::Puzzle;
+use laser_puzzle::sat;
+use laser_puzzle::session::GameSession;
+use laser_puzzle::solver;
+use laser_puzzle::solver::{GridKnowledge, UncertainGrid};
+use laser_puzzle::svg;
+use laser_puzzle::symmetry;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::io::{BufRead, Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[derive(Parser)]
+#[command(
+    name = "laser-puzzle",
+    about = "Generate, solve and play black box laser puzzles."
+)]
+pub struct Cli {
+    /// How results are printed.
+    #[arg(long, value_enum, default_value_t = Format::Text, global = true)]
+    format: Format,
+    /// The variant of the laser rules, classic or plain.
+    #[arg(long, default_value_t = Ruleset::Classic, global = true)]
+    rules: Ruleset,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a random puzzle with a unique solution.
+    Generate {
+        /// Width and height of the box. Only the default size is supported right now.
+        #[arg(long, default_value_t = GRID_SIZE)]
+        size: usize,
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u8).range(0..=64))]
+        atoms: u8,
+        /// Seed for reproducible puzzles. A random seed is used and reported otherwise.
+        #[arg(long)]
+        seed: Option<u64>,
+        /// One of easy, medium or hard. Any difficulty is accepted if left out.
+        #[arg(long)]
+        difficulty: Option<Difficulty>,
+        /// Show the atoms in the generated puzzle.
+        #[arg(long)]
+        solution: bool,
+    },
+    /// Solve a puzzle file, or read the puzzle from stdin.
+    Solve {
+        file: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = Method::BruteForce)]
+        method: Method,
+        /// A cell known to hold an atom, e.g. '--atom 2,3'. Can be given several times.
+        #[arg(long = "atom", value_parser = parse_position)]
+        atoms: Vec<I8Vec2>,
+        /// A cell known to be empty, e.g. '--empty 0,0'. Can be given several times.
+        #[arg(long = "empty", value_parser = parse_position)]
+        empty: Vec<I8Vec2>,
+    },
+    /// Check that a puzzle file (or stdin) has exactly one solution.
+    Verify {
+        file: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = Method::BruteForce)]
+        method: Method,
+    },
+    /// Rotate and mirror a puzzle file (or stdin) into its canonical form. Puzzles which only
+    /// differ by symmetry end up with the same canonical form.
+    Canonical { file: Option<PathBuf> },
+    /// Go through every layout with a few atoms and count how many a fully probed board
+    /// identifies.
+    Enumerate {
+        /// Up to 5 atoms, more would take too long.
+        #[arg(long, default_value_t = 3)]
+        atoms: u8,
+        /// Write all layouts, grouped by their observations, to this file.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Play a random puzzle interactively.
+    Play {
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u8).range(0..=64))]
+        atoms: u8,
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Which guesses are right: only the hidden atoms (exact), or any atoms which look the
+        /// same from all sides (observations).
+        #[arg(long, default_value_t = Grading::Exact)]
+        grading: Grading,
+    },
+    /// Show where a laser shone into a grid ends up.
+    Trace {
+        /// The grid, as printed by `AtomGrid::as_bitboard`. Uses a random grid if left out.
+        #[arg(long)]
+        bitboard: Option<u64>,
+        /// Number of atoms in the random grid.
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u8).range(0..=64))]
+        atoms: u8,
+        /// The side of the box the laser is shone in from.
+        #[arg(value_enum)]
+        side: Side,
+        /// Row or column of the laser, starting at 0 in the top left.
+        shift: u8,
+    },
+    /// Measure how long the engine takes for its main operations.
+    Bench {
+        #[arg(long, default_value_t = 1000)]
+        iterations: u32,
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u8).range(0..=64))]
+        atoms: u8,
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Draw a puzzle file (or stdin) as an SVG image.
+    Render {
+        file: Option<PathBuf>,
+        /// Draw the atoms of this grid, as printed by `AtomGrid::as_bitboard`.
+        #[arg(long)]
+        solution: Option<u64>,
+        /// Shade the cells the solver rules out.
+        #[arg(long)]
+        knowledge: bool,
+        /// Draw the path of a laser, e.g. 'left:3'. Needs the solution and can be given several
+        /// times.
+        #[arg(long = "path", value_parser = parse_entry)]
+        paths: Vec<(Side, u8)>,
+        /// Width and height of a cell in pixels.
+        #[arg(long, default_value_t = 40.0)]
+        cell_size: f64,
+        #[arg(long)]
+        background: Option<String>,
+        #[arg(long)]
+        line_color: Option<String>,
+        #[arg(long)]
+        atom_color: Option<String>,
+        /// Color of the laser paths, give it several times for one color per path.
+        #[arg(long = "path-color")]
+        path_colors: Vec<String>,
+        /// Write the image to this file instead of printing it.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Turn a puzzle file (or stdin) into a web page for playing it in the browser. The page
+    /// checks the player's atoms against the solution, so the puzzle must have exactly one.
+    Html {
+        file: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = Method::BruteForce)]
+        method: Method,
+        #[arg(long, default_value = "Laser Puzzle")]
+        title: String,
+        /// Write the HTML to this file instead of printing it.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Generate a printable booklet of puzzles from easy to hard, with the solutions in the back.
+    Booklet {
+        /// Number of puzzles.
+        #[arg(long, default_value_t = 12)]
+        count: usize,
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u8).range(0..=64))]
+        atoms: u8,
+        #[arg(long)]
+        seed: Option<u64>,
+        #[arg(long, default_value = "Laser Puzzles")]
+        title: String,
+        /// Write the HTML to this file instead of printing it.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Report how much the rule based solver finds out on random puzzles.
+    Stats {
+        /// Number of random puzzles for each atom count.
+        #[arg(long, default_value_t = 1000)]
+        puzzles: u32,
+        /// Atom counts to look at, e.g. '--atoms 3,4,5'.
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_values_t = [3, 4, 5, 6, 8],
+            value_parser = clap::value_parser!(u8).range(0..=64)
+        )]
+        atoms: Vec<u8>,
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+}
+
+/// How to search for the solutions of a puzzle.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Method {
+    /// Try all placements of atoms in the cells the solver can't rule out.
+    BruteForce,
+    /// Encode the laser rules as clauses for a SAT solver.
+    Sat,
+}
+
+impl Method {
+    fn find_solutions(self, puzzle: &Puzzle, limit: usize) -> Vec<AtomGrid> {
+        match self {
+            Method::BruteForce => brute_force::find_solutions(puzzle, limit),
+            Method::Sat => sat::find_solutions(puzzle, limit),
+        }
+    }
+}
+
+/// A side of the box, as seen by the player.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum Side {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Side {
+    /// The direction a laser travels in when shone in from this side.
+    fn entry_direction(self) -> Direction {
+        match self {
+            Side::Top => Down,
+            Side::Bottom => Up,
+            Side::Left => Right,
+            Side::Right => Left,
+        }
+    }
+
+    fn of_entry_direction(direction: Direction) -> Self {
+        match direction {
+            Down => Side::Top,
+            Up => Side::Bottom,
+            Right => Side::Left,
+            Left => Side::Right,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Side::Top => "top",
+            Side::Bottom => "bottom",
+            Side::Left => "left",
+            Side::Right => "right",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "top" | "t" => Some(Side::Top),
+            "bottom" | "b" => Some(Side::Bottom),
+            "left" | "l" => Some(Side::Left),
+            "right" | "r" => Some(Side::Right),
+            _ => None,
+        }
+    }
+}
+
+pub fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let format = cli.format;
+    let rules = cli.rules;
+    match cli.command {
+        Command::Generate {
+            size,
+            atoms,
+            seed,
+            difficulty,
+            solution,
+        } => generate(format, rules, size, atoms, seed, difficulty, solution),
+        Command::Solve {
+            file,
+            method,
+            atoms,
+            empty,
+        } => {
+            let mut known_atoms = AtomGrid::default();
+            atoms.into_iter().for_each(|v| known_atoms.set(v, true));
+            let mut known_empty = AtomGrid::default();
+            empty.into_iter().for_each(|v| known_empty.set(v, true));
+            let known = UncertainGrid::from_known(&known_atoms, &known_empty)?;
+            solve(format, method, &read_puzzle(file)?, known)
+        }
+        Command::Verify { file, method } => verify(format, method, &read_puzzle(file)?),
+        Command::Canonical { file } => canonical(format, &read_puzzle(file)?),
+        Command::Enumerate { atoms, output } => enumerate(format, rules, atoms, output),
+        Command::Play {
+            atoms,
+            seed,
+            grading,
+        } => play(
+            rules,
+            atoms,
+            seed.unwrap_or_else(rand::random),
+            grading,
+            std::io::stdin().lock(),
+            std::io::stdout(),
+        ),
+        Command::Trace {
+            bitboard,
+            atoms,
+            side,
+            shift,
+        } => {
+            let grid = match bitboard {
+                Some(bitboard) => AtomGrid::from_bitboard(bitboard),
+                None => AtomGrid::random(atoms),
+            };
+            trace(format, rules, &grid, side, shift)
+        }
+        Command::Bench {
+            iterations,
+            atoms,
+            seed,
+        } => bench(
+            format,
+            rules,
+            iterations,
+            atoms,
+            seed.unwrap_or_else(rand::random),
+        ),
+        Command::Render {
+            file,
+            solution,
+            knowledge,
+            paths,
+            cell_size,
+            background,
+            line_color,
+            atom_color,
+            path_colors,
+            output,
+        } => {
+            let default = svg::Style::default();
+            let style = svg::Style {
+                cell_size,
+                background: background.unwrap_or(default.background),
+                line_color: line_color.unwrap_or(default.line_color),
+                atom_color: atom_color.unwrap_or(default.atom_color),
+                path_colors: if path_colors.is_empty() {
+                    default.path_colors
+                } else {
+                    path_colors
+                },
+                ..default
+            };
+            let solution = solution.map(AtomGrid::from_bitboard);
+            render(
+                format,
+                &read_puzzle(file)?,
+                solution.as_ref(),
+                knowledge,
+                &paths,
+                &style,
+                output,
+            )
+        }
+        Command::Html {
+            file,
+            method,
+            title,
+            output,
+        } => {
+            let puzzle = read_puzzle(file)?;
+            let solution = match method.find_solutions(&puzzle, 2)[..] {
+                [solution] => solution,
+                [] => return Err("the puzzle has no solution".into()),
+                _ => return Err("the puzzle has more than one solution".into()),
+            };
+            let html = page::html(&title, &puzzle, &solution)?;
+            match output {
+                Some(path) => std::fs::write(path, &html)?,
+                None => match format {
+                    Format::Text => print!("{}", html),
+                    Format::Json => println!("{}", json!({ "html": html })),
+                },
+            }
+            Ok(())
+        }
+        Command::Booklet {
+            count,
+            atoms,
+            seed,
+            title,
+            output,
+        } => {
+            let seed = seed.unwrap_or_else(rand::random);
+            let entries = booklet::generate(count, atoms, rules, &mut StdRng::seed_from_u64(seed));
+            let html = booklet::html(&title, &entries)?;
+            match output {
+                Some(path) => std::fs::write(path, &html)?,
+                None if format == Format::Text => print!("{}", html),
+                None => {}
+            }
+            match format {
+                Format::Text => eprintln!("{} puzzles, seed {}", entries.len(), seed),
+                Format::Json => {
+                    let puzzles: Vec<Value> = entries
+                        .iter()
+                        .map(|entry| {
+                            json!({
+                                "difficulty": entry.difficulty.to_string(),
+                                "puzzle": puzzle_json(&entry.puzzle),
+                                "solution": entry.grid.as_bitboard(),
+                            })
+                        })
+                        .collect();
+                    println!("{}", json!({ "seed": seed, "puzzles": puzzles }));
+                }
+            }
+            Ok(())
+        }
+        Command::Stats {
+            puzzles,
+            atoms,
+            seed,
+        } => stats(
+            format,
+            rules,
+            puzzles,
+            &atoms,
+            seed.unwrap_or_else(rand::random),
+        ),
+    }
+}
+
+fn read_puzzle(file: Option<PathBuf>) -> Result<Puzzle, Box<dyn Error>> {
+    let text = match file {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text)?;
+            text
+        }
+    };
+    Ok(text.parse()?)
+}
+
+fn generate(
+    format: Format,
+    rules: Ruleset,
+    size: usize,
+    atoms: u8,
+    seed: Option<u64>,
+    difficulty: Option<Difficulty>,
+    solution: bool,
+) -> Result<(), Box<dyn Error>> {
+    if size != GRID_SIZE {
+        return Err(format!("only {0}x{0} boards are supported", GRID_SIZE).into());
+    }
+    let seed = seed.unwrap_or_else(rand::random);
+    let (grid, puzzle) =
+        generator::generate(atoms, rules, difficulty, &mut StdRng::seed_from_u64(seed))
+            .ok_or("no puzzle with a unique solution found, try fewer atoms or another seed")?;
+    let difficulty = Difficulty::of(&puzzle);
+
+    match format {
+        Format::Text => {
+            eprintln!("seed: {}, difficulty: {}", seed, difficulty);
+            if solution {
+                if rules != Ruleset::Classic {
+                    println!("rules: {}", rules);
+                }
+                print!("{}", observation::draw(&grid, &puzzle.observations)?);
+            } else {
+                print!("{}", puzzle);
+            }
+        }
+        Format::Json => {
+            let mut result = json!({
+                "seed": seed,
+                "difficulty": difficulty.to_string(),
+                "puzzle": puzzle_json(&puzzle),
+            });
+            if solution {
+                result["solution"] = json!(grid.as_bitboard());
+            }
+            println!("{}", result);
+        }
+    }
+    Ok(())
+}
+
+fn solve(
+    format: Format,
+    method: Method,
+    puzzle: &Puzzle,
+    known: UncertainGrid,
+) -> Result<(), Box<dyn Error>> {
+    let knowledge = solver::solve_from(known, &puzzle.observations)?;
+    let solutions = method.find_solutions(puzzle, 2);
+
+    match format {
+        Format::Text => {
+            if knowledge.to_atom_grid().is_some() {
+                println!("Every cell is known:");
+            } else {
+                println!("Deduced by the rules:");
+            }
+            print!("{}", solver::draw(&knowledge, &puzzle.observations)?);
+            match solutions.as_slice() {
+                [] => println!("No solution."),
+                [grid] => {
+                    println!("Unique solution:");
+                    print!("{}", observation::draw(grid, &puzzle.observations)?);
+                }
+                [grid, other, ..] => {
+                    let cells: Vec<String> = ambiguous_cells(grid, other)
+                        .atoms()
+                        .map(|v| format!("{},{}", v.x, v.y))
+                        .collect();
+                    println!("Several solutions, they differ at {}.", cells.join(" "));
+                    println!("For example:");
+                    print!("{}", observation::draw(grid, &puzzle.observations)?);
+                }
+            }
+        }
+        Format::Json => {
+            let mut result = json!({
+                "knowledge": knowledge_json(&knowledge),
+                "unknown_cells": knowledge.count(GridKnowledge::Unknown),
+                "unique": solutions.len() == 1,
+                "solutions": solutions.iter().map(AtomGrid::as_bitboard).collect::<Vec<_>>(),
+            });
+            if let [grid, other, ..] = solutions.as_slice() {
+                result["ambiguous"] = json!(ambiguous_cells(grid, other)
+                    .atoms()
+                    .map(|v| [v.x, v.y])
+                    .collect::<Vec<_>>());
+            }
+            println!("{}", result);
+        }
+    }
+    Ok(())
+}
+
+/// Cells which hold an atom in only one of the two solutions.
+fn ambiguous_cells(grid: &AtomGrid, other: &AtomGrid) -> AtomGrid {
+    grid.union(other).difference(&grid.intersection(other))
+}
+
+fn verify(format: Format, method: Method, puzzle: &Puzzle) -> Result<(), Box<dyn Error>> {
+    let solutions = method.find_solutions(puzzle, 2);
+    // How far the rules get on their own, if there is a single solution to compare with.
+    let comparison = match solutions.as_slice() {
+        [grid] => Some(solver::solve_as_much_as_you_can(&puzzle.observations).compare(grid)),
+        _ => None,
+    };
+
+    match format {
+        Format::Text => match solutions.len() {
+            0 => println!("No solution."),
+            1 => {
+                println!("Unique.");
+                if let Some(comparison) = comparison {
+                    println!(
+                        "The rules alone decide {} of {} cells, {} of them wrongly.",
+                        comparison.correct.len() + comparison.wrong.len(),
+                        GRID_SIZE * GRID_SIZE,
+                        comparison.wrong.len()
+                    );
+                }
+            }
+            _ => println!("Not unique, there are several solutions."),
+        },
+        Format::Json => {
+            let mut result = json!({
+                "unique": solutions.len() == 1,
+                "solvable": !solutions.is_empty(),
+            });
+            if let Some(comparison) = comparison {
+                result["rules"] = json!({
+                    "correct": comparison.correct.len(),
+                    "wrong": comparison.wrong.len(),
+                    "unknown": comparison.unknown.len(),
+                });
+            }
+            println!("{}", result);
+        }
+    }
+    if solutions.len() != 1 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+const PLAY_HELP: &str = "\
+Commands:
+  probe <side> <shift>   shine a laser in, e.g. 'probe left 3' or 'p l 3'
+  show                   show all observations so far
+  guess <x,y> ...        guess all atom positions, e.g. 'guess 0,1 4,4 7,2'
+  reveal                 show the solution and end the game
+  help                   show this help
+  quit                   end the game";
+
+fn play(
+    rules: Ruleset,
+    atoms: u8,
+    seed: u64,
+    grading: Grading,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let grid = AtomGrid::random_with(atoms, &mut StdRng::seed_from_u64(seed));
+    let mut session = GameSession::new(grid, rules);
+    let mut probes = 0;
+
+    writeln!(
+        output,
+        "There are {} atoms hidden in the box (seed {}).",
+        atoms, seed
+    )?;
+    writeln!(output, "{}", PLAY_HELP)?;
+    write!(output, "> ")?;
+    output.flush()?;
+
+    for line in input.lines() {
+        let line = line?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => {}
+            ["probe" | "p", side, shift] => {
+                let observed = match (Side::parse(side), shift.parse::<u8>()) {
+                    (Some(side), Ok(shift)) => {
+                        let direction = side.entry_direction();
+                        session.probe(shift, direction).ok().map(|_| {
+                            session.observations().sides[direction as usize][shift as usize]
+                        })
+                    }
+                    _ => None,
+                };
+                match observed {
+                    Some(obs) => {
+                        probes += 1;
+                        writeln!(output, "{}", obs)?;
+                    }
+                    None => writeln!(output, "Usage: probe <top|bottom|left|right> <0-7>")?,
+                }
+            }
+            ["show" | "s"] => {
+                write!(
+                    output,
+                    "{}",
+                    solver::draw(&UncertainGrid::default(), session.observations())?
+                )?;
+            }
+            ["guess" | "g", positions @ ..] => match parse_guess(positions) {
+                Some(guess) => {
+                    let checked = session.guess(guess);
+                    if session.grade(&guess, grading) {
+                        writeln!(output, "Correct! You needed {} probes.", probes)?;
+                        if checked.verdict != Verdict::Correct {
+                            let hidden: Vec<String> =
+                                grid.atoms().map(|v| format!("{},{}", v.x, v.y)).collect();
+                            writeln!(
+                                output,
+                                "The hidden atoms were at {}, which looks just the same.",
+                                hidden.join(" ")
+                            )?;
+                        }
+                        return Ok(());
+                    }
+                    match checked.verdict {
+                        Verdict::Correct | Verdict::Alternative => writeln!(
+                            output,
+                            "Those atoms fit all probes so far, but they are not the hidden \
+                             ones. Keep probing to tell them apart."
+                        )?,
+                        Verdict::AtomCount { hidden, .. } => writeln!(
+                            output,
+                            "Those atoms fit all probes so far, but the box holds {}.",
+                            hidden
+                        )?,
+                        Verdict::Wrong => {
+                            writeln!(output, "That is not it:")?;
+                            for difference in &checked.differences {
+                                writeln!(output, "  {}", difference)?;
+                            }
+                        }
+                    }
+                }
+                None => writeln!(output, "Usage: guess <x,y> <x,y> ...")?,
+            },
+            ["reveal"] => {
+                write!(
+                    output,
+                    "{}",
+                    observation::draw(&grid, session.observations())?
+                )?;
+                return Ok(());
+            }
+            ["help" | "h"] => writeln!(output, "{}", PLAY_HELP)?,
+            ["quit" | "q"] => return Ok(()),
+            _ => writeln!(output, "Unknown command, type 'help' for a list.")?,
+        }
+        write!(output, "> ")?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+fn parse_guess(positions: &[&str]) -> Option<AtomGrid> {
+    let mut guess = AtomGrid::default();
+    for position in positions {
+        guess.set(parse_position(position).ok()?, true);
+    }
+    Some(guess)
+}
+
+/// Reads a cell given as `x,y`.
+fn parse_position(position: &str) -> Result<I8Vec2, String> {
+    let error = || format!("'{}' is not a cell like 2,3", position);
+    let (x, y) = position.split_once(',').ok_or_else(error)?;
+    let v = I8Vec2::new(
+        x.trim().parse().map_err(|_| error())?,
+        y.trim().parse().map_err(|_| error())?,
+    );
+    if !v.in_grid() {
+        return Err(format!("{},{} is outside the box", v.x, v.y));
+    }
+    Ok(v)
+}
+
+fn canonical(format: Format, puzzle: &Puzzle) -> Result<(), Box<dyn Error>> {
+    let (symmetry, canonical) = symmetry::canonical(puzzle);
+    match format {
+        Format::Text => {
+            eprintln!("symmetry: {}", symmetry);
+            print!("{}", canonical);
+        }
+        Format::Json => println!(
+            "{}",
+            json!({
+                "symmetry": symmetry.to_string(),
+                "puzzle": puzzle_json(&canonical),
+            })
+        ),
+    }
+    Ok(())
+}
+
+fn enumerate(
+    format: Format,
+    rules: Ruleset,
+    atoms: u8,
+    output: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    if atoms > 5 {
+        return Err("enumerating more than 5 atoms takes too long".into());
+    }
+    let database = Database::build(atoms, rules);
+    if let Some(path) = output {
+        database.write(std::io::BufWriter::new(std::fs::File::create(path)?))?;
+    }
+
+    let layouts = database.layouts();
+    let unique = database.unique_layouts();
+    match format {
+        Format::Text => {
+            println!("{} atoms, {} rules", atoms, rules);
+            println!("layouts               {:>10}", layouts);
+            println!("distinct observations {:>10}", database.classes.len());
+            println!(
+                "uniquely determined   {:>10} ({:.1}%)",
+                unique,
+                100.0 * unique as f64 / layouts as f64
+            );
+            println!("largest ambiguity     {:>10}", database.largest_class());
+        }
+        Format::Json => println!(
+            "{}",
+            json!({
+                "atoms": atoms,
+                "layouts": layouts,
+                "distinct_observations": database.classes.len(),
+                "uniquely_determined": unique,
+                "largest_ambiguity": database.largest_class(),
+            })
+        ),
+    }
+    Ok(())
+}
+
+fn trace(
+    format: Format,
+    rules: Ruleset,
+    grid: &AtomGrid,
+    side: Side,
+    shift: u8,
+) -> Result<(), Box<dyn Error>> {
+    if shift as usize >= GRID_SIZE {
+        return Err(format!("shift must be below {}", GRID_SIZE).into());
+    }
+    let laser = LaserTip::new(shift, side.entry_direction());
+    let moves = laser.traverse_grid(grid, rules).moves();
+    let result = observation::shoot(laser, grid, rules);
+    let path = laser.record_path(grid, rules);
+
+    match format {
+        Format::Text => {
+            println!("BitBoard: {}", grid.as_bitboard());
+            print!("{}", laser::draw_path(grid, &path)?);
+            println!("(+ turned, * reflected, × absorbed)");
+            match result {
+                ProbeResult::Absorbed => println!("Absorbed after {} moves.", moves),
+                ProbeResult::Reflected => println!("Reflected after {} moves.", moves),
+                ProbeResult::Exit(out_shift, out_direction) => println!(
+                    "Exits {} {} after {} moves.",
+                    Side::of_entry_direction(out_direction).name(),
+                    out_shift,
+                    moves
+                ),
+            }
+        }
+        Format::Json => {
+            let steps: Vec<Value> = path
+                .steps()
+                .into_iter()
+                .map(|(position, step)| {
+                    let step = match step {
+                        PathStep::Straight(direction) => direction.arrow().to_string(),
+                        PathStep::Turn => "turn".to_owned(),
+                        PathStep::Reflection => "reflection".to_owned(),
+                        PathStep::Absorption => "absorption".to_owned(),
+                    };
+                    json!({ "x": position.x, "y": position.y, "step": step })
+                })
+                .collect();
+            let mut json = json!({
+                "bitboard": grid.as_bitboard(),
+                "side": side.name(),
+                "shift": shift,
+                "moves": moves,
+                "path": steps,
+            });
+            match result {
+                ProbeResult::Absorbed => json["result"] = json!("absorbed"),
+                ProbeResult::Reflected => json["result"] = json!("reflected"),
+                ProbeResult::Exit(out_shift, out_direction) => {
+                    json["result"] = json!("exit");
+                    json["exit"] = json!({
+                        "side": Side::of_entry_direction(out_direction).name(),
+                        "shift": out_shift,
+                    });
+                }
+            }
+            println!("{}", json);
+        }
+    }
+    Ok(())
+}
+
+/// Runs the operation `iterations` times and returns the average time per run.
+fn measure(iterations: u32, mut operation: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        operation();
+    }
+    start.elapsed() / iterations.max(1)
+}
+
+fn bench(
+    format: Format,
+    rules: Ruleset,
+    iterations: u32,
+    atoms: u8,
+    seed: u64,
+) -> Result<(), Box<dyn Error>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let grids: Vec<AtomGrid> = (0..iterations)
+        .map(|_| AtomGrid::random_with(atoms, &mut rng))
+        .collect();
+    let puzzles: Vec<Puzzle> = grids
+        .iter()
+        .map(|g| Puzzle::from_grid(g, atoms, rules))
+        .collect();
+
+    let mut i = 0;
+    let mut next = || {
+        i = (i + 1) % grids.len();
+        i
+    };
+    let results = [
+        (
+            "observe_all",
+            measure(iterations, || {
+                std::hint::black_box(Observations::observe_all(&grids[next()], rules));
+            }),
+        ),
+        (
+            "observe_all_fast",
+            measure(iterations, || {
+                std::hint::black_box(Observations::observe_all_fast(&grids[next()], rules));
+            }),
+        ),
+        (
+            "solve_as_much_as_you_can",
+            measure(iterations, || {
+                std::hint::black_box(solver::solve_as_much_as_you_can(
+                    &puzzles[next()].observations,
+                ));
+            }),
+        ),
+        (
+            "find_solutions",
+            measure(iterations.min(100), || {
+                std::hint::black_box(brute_force::find_solutions(&puzzles[next()], 2));
+            }),
+        ),
+        (
+            "sat::find_solutions",
+            measure(iterations.min(100), || {
+                std::hint::black_box(sat::find_solutions(&puzzles[next()], 2));
+            }),
+        ),
+    ];
+
+    let speed_up = results[0].1.as_secs_f64() / results[1].1.as_secs_f64();
+
+    match format {
+        Format::Text => {
+            println!("{} atoms, seed {}", atoms, seed);
+            for (name, duration) in results {
+                println!("{:<26} {:>12.3?}", name, duration);
+            }
+            println!("observe_all_fast speed-up  {:>11.1}x", speed_up);
+        }
+        Format::Json => {
+            let timings: serde_json::Map<String, Value> = results
+                .iter()
+                .map(|(name, duration)| (name.to_string(), json!(duration.as_nanos() as u64)))
+                .collect();
+            println!(
+                "{}",
+                json!({
+                    "atoms": atoms,
+                    "seed": seed,
+                    "iterations": iterations,
+                    "nanos": timings,
+                    "speed_up": speed_up,
+                })
+            );
+        }
+    }
+    Ok(())
+}
+
+fn render(
+    format: Format,
+    puzzle: &Puzzle,
+    solution: Option<&AtomGrid>,
+    knowledge: bool,
+    entries: &[(Side, u8)],
+    style: &svg::Style,
+    output: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let observations = &puzzle.observations;
+    let knowledge = knowledge.then(|| solver::solve_as_much_as_you_can(observations));
+    let paths = match solution {
+        Some(grid) => entries
+            .iter()
+            .map(|&(side, shift)| {
+                LaserTip::new(shift, side.entry_direction()).record_path(grid, observations.ruleset)
+            })
+            .collect(),
+        None if entries.is_empty() => vec![],
+        None => return Err("laser paths need the solution".into()),
+    };
+    let image = svg::render(
+        &svg::Drawing {
+            observations,
+            solution,
+            knowledge: knowledge.as_ref(),
+            paths: &paths,
+        },
+        style,
+    )?;
+
+    match output {
+        Some(path) => std::fs::write(path, &image)?,
+        None => match format {
+            Format::Text => print!("{}", image),
+            Format::Json => println!("{}", json!({ "svg": image })),
+        },
+    }
+    Ok(())
+}
+
+/// Reads a laser entry given as `side:shift`, e.g. `left:3`.
+fn parse_entry(entry: &str) -> Result<(Side, u8), String> {
+    let error = || format!("'{}' is not a laser entry like left:3", entry);
+    let (side, shift) = entry.split_once(':').ok_or_else(error)?;
+    let side = Side::parse(side.trim()).ok_or_else(error)?;
+    let shift: u8 = shift.trim().parse().map_err(|_| error())?;
+    if shift as usize >= GRID_SIZE {
+        return Err(format!("shift must be below {}", GRID_SIZE));
+    }
+    Ok((side, shift))
+}
+
+fn stats(
+    format: Format,
+    rules: Ruleset,
+    puzzles: u32,
+    atom_counts: &[u8],
+    seed: u64,
+) -> Result<(), Box<dyn Error>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let cells = GRID_SIZE * GRID_SIZE;
+    let mut rows = vec![];
+    for &atoms in atom_counts {
+        let mut known = 0;
+        let mut solved = 0;
+        let mut deductions = vec![0; solver::Rule::all().len()];
+        for _ in 0..puzzles {
+            let grid = AtomGrid::random_with(atoms, &mut rng);
+            let observations = Observations::observe_all_fast(&grid, rules);
+            let (knowledge, counts) =
+                solver::solve_counting(UncertainGrid::default(), &observations)?;
+            let unknown = knowledge.count(GridKnowledge::Unknown);
+            known += cells - unknown;
+            // With the number of atoms given, the last unknown cells must be the atoms.
+            if unknown == atoms as usize {
+                solved += 1;
+            }
+            for (rule, count) in counts {
+                deductions[rule as usize] += count;
+            }
+        }
+        rows.push((atoms, known, solved, deductions));
+    }
+
+    let per_puzzle = |count: usize| count as f64 / puzzles.max(1) as f64;
+    match format {
+        Format::Text => {
+            println!("{} puzzles per atom count, seed {}", puzzles, seed);
+            print!("atoms  cells known  fully solved");
+            for rule in solver::Rule::all() {
+                print!("  {:>10}", rule.name());
+            }
+            println!();
+            for (atoms, known, solved, deductions) in &rows {
+                print!(
+                    "{:>5}  {:>10.1}%  {:>11.1}%",
+                    atoms,
+                    100.0 * per_puzzle(*known) / cells as f64,
+                    100.0 * per_puzzle(*solved)
+                );
+                for count in deductions {
+                    print!("  {:>10.1}", per_puzzle(*count));
+                }
+                println!();
+            }
+            println!("Rule columns are the cells found per puzzle, in the order the rules run.");
+        }
+        Format::Json => {
+            let rows: Vec<Value> = rows
+                .iter()
+                .map(|(atoms, known, solved, deductions)| {
+                    let rules: serde_json::Map<String, Value> = solver::Rule::all()
+                        .iter()
+                        .zip(deductions)
+                        .map(|(rule, &count)| (rule.name().to_string(), json!(count)))
+                        .collect();
+                    json!({
+                        "atoms": atoms,
+                        "known_cells": known,
+                        "fully_solved": solved,
+                        "deductions": rules,
+                    })
+                })
+                .collect();
+            println!(
+                "{}",
+                json!({
+                    "puzzles": puzzles,
+                    "seed": seed,
+                    "cells": cells,
+                    "results": rows,
+                })
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atom_counts_must_fit_into_the_box() {
+        let args = |command: &'static str, atoms: &'static str| {
+            let mut args = vec!["laser-puzzle", command, "--atoms", atoms];
+            if command == "trace" {
+                args.extend(["left", "0"]);
+            }
+            args
+        };
+        for command in ["generate", "play", "trace", "bench", "booklet", "stats"] {
+            assert!(
+                Cli::try_parse_from(args(command, "64")).is_ok(),
+                "{}",
+                command
+            );
+            assert!(
+                Cli::try_parse_from(args(command, "65")).is_err(),
+                "{}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn play_a_game_until_the_correct_guess() {
+        let grid = AtomGrid::random_with(3, &mut StdRng::seed_from_u64(3));
+        let guess: Vec<String> = grid.atoms().map(|v| format!("{},{}", v.x, v.y)).collect();
+        let input = format!("probe left 3\nshow\nguess 0,0\nguess {}\n", guess.join(" "));
+
+        let mut output = vec![];
+        play(
+            Ruleset::Classic,
+            3,
+            3,
+            Grading::Exact,
+            input.as_bytes(),
+            &mut output,
+        )
+        .expect("play works");
+        let output = String::from_utf8(output).unwrap();
+        assert!(
+            output.contains("Correct! You needed 1 probes."),
+            "{}",
+            output
+        );
+        assert!(
+            output.contains("That is not it:\n  left 3: the laser is absorbed"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn alternative_solutions_can_be_accepted() {
+        let (seed, other) = (0..)
+            .find_map(|seed| {
+                let grid = AtomGrid::random_with(4, &mut StdRng::seed_from_u64(seed));
+                let puzzle = Puzzle::from_grid(&grid, 4, Ruleset::Classic);
+                let solutions = brute_force::find_solutions(&puzzle, 2);
+                let other = solutions.into_iter().find(|&other| other != grid)?;
+                Some((seed, other))
+            })
+            .unwrap();
+        let guess: Vec<String> = other.atoms().map(|v| format!("{},{}", v.x, v.y)).collect();
+        let input = format!("guess {}\n", guess.join(" "));
+
+        for (grading, accepted) in [(Grading::Exact, false), (Grading::Observations, true)] {
+            let mut output = vec![];
+            play(
+                Ruleset::Classic,
+                4,
+                seed,
+                grading,
+                input.as_bytes(),
+                &mut output,
+            )
+            .expect("play works");
+            let output = String::from_utf8(output).unwrap();
+            assert_eq!(output.contains("The hidden atoms were at"), accepted);
+        }
+    }
+}
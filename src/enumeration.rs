@@ -0,0 +1,136 @@
+//! Goes through every possible layout with a few atoms and groups the layouts by what a fully probed
+//! board shows. A layout is uniquely determined if no other layout shows the same observations.
+//!
+//! The number of layouts grows quickly with the number of atoms, up to five atoms are feasible.
+
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::laser::Ruleset;
+use crate::observation::Observations;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// All layouts with the given number of atoms, grouped by their observations.
+pub struct Database {
+    /// Number of atoms in every layout.
+    pub atom_count: u8,
+    /// The rules the observations were made with.
+    pub ruleset: Ruleset,
+    /// Layouts which can not be told apart, in the order they were found.
+    pub classes: Vec<Vec<AtomGrid>>,
+}
+
+impl Database {
+    /// Observes every layout. Takes seconds for four atoms and minutes for five.
+    pub fn build(atom_count: u8, ruleset: Ruleset) -> Self {
+        let mut index: HashMap<Observations, usize> = HashMap::new();
+        let mut classes: Vec<Vec<AtomGrid>> = vec![];
+        for grid in layouts(atom_count) {
+            let observations = Observations::observe_all_fast(&grid, ruleset);
+            let i = *index.entry(observations).or_insert_with(|| {
+                classes.push(vec![]);
+                classes.len() - 1
+            });
+            classes[i].push(grid);
+        }
+        Database {
+            atom_count,
+            ruleset,
+            classes,
+        }
+    }
+
+    /// The number of layouts, the same as [layouts] returns.
+    pub fn layouts(&self) -> usize {
+        self.classes.iter().map(Vec::len).sum()
+    }
+
+    /// Layouts which are the only explanation of their observations.
+    pub fn unique_layouts(&self) -> usize {
+        self.classes.iter().filter(|class| class.len() == 1).count()
+    }
+
+    /// The most layouts sharing the same observations.
+    pub fn largest_class(&self) -> usize {
+        self.classes.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    /// Writes a summary as `#` comments, followed by one line per class with the bitboards of its
+    /// layouts, see [AtomGrid::as_bitboard].
+    pub fn write(&self, mut out: impl Write) -> std::io::Result<()> {
+        writeln!(out, "# atoms: {}", self.atom_count)?;
+        writeln!(out, "# rules: {}", self.ruleset)?;
+        writeln!(out, "# layouts: {}", self.layouts())?;
+        writeln!(out, "# uniquely determined: {}", self.unique_layouts())?;
+        for class in &self.classes {
+            let bitboards: Vec<String> = class
+                .iter()
+                .map(|grid| grid.as_bitboard().to_string())
+                .collect();
+            writeln!(out, "{}", bitboards.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Every grid with exactly `atom_count` atoms, in increasing order of [AtomGrid::bits]. None if
+/// there are more atoms than cells.
+pub fn layouts(atom_count: u8) -> impl Iterator<Item = AtomGrid> {
+    let first = match atom_count as usize {
+        count if count > GRID_SIZE * GRID_SIZE => None,
+        count if count == GRID_SIZE * GRID_SIZE => Some(u64::MAX),
+        count => Some((1u64 << count) - 1),
+    };
+    let mut next = first;
+    std::iter::from_fn(move || {
+        let bits = next?;
+        next = next_combination(bits);
+        Some(AtomGrid::from_bits(bits))
+    })
+}
+
+/// The next larger number with the same number of bits set, or None if there is none.
+fn next_combination(bits: u64) -> Option<u64> {
+    if bits == 0 {
+        return None;
+    }
+    // Gosper's hack: move the lowest block of ones up by one and the rest of it back down.
+    let lowest = bits & bits.wrapping_neg();
+    let ripple = bits.checked_add(lowest)?;
+    let ones = ((bits ^ ripple) >> 2) / lowest;
+    Some(ripple | ones)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brute_force;
+    use crate::puzzle::Puzzle;
+
+    #[test]
+    fn all_layouts_are_enumerated() {
+        assert_eq!(layouts(0).count(), 1);
+        assert_eq!(layouts(1).count(), 64);
+        assert_eq!(layouts(2).count(), 2016);
+        assert!(layouts(2).all(|grid| grid.atom_count() == 2));
+        assert_eq!(layouts(64).count(), 1);
+        assert_eq!(layouts(65).count(), 0);
+        assert_eq!(layouts(u8::MAX).count(), 0);
+    }
+
+    /// The database is the ground truth for the brute force search, which relies on the solver.
+    #[test]
+    fn brute_force_agrees_with_the_database() {
+        for ruleset in [Ruleset::Classic, Ruleset::Plain] {
+            let database = Database::build(2, ruleset);
+            assert_eq!(database.layouts(), 2016);
+            for class in &database.classes {
+                let puzzle = Puzzle::from_grid(&class[0], 2, ruleset);
+                let mut solutions = brute_force::find_solutions(&puzzle, usize::MAX);
+                solutions.sort();
+                let mut class = class.clone();
+                class.sort();
+                assert_eq!(solutions, class);
+            }
+        }
+    }
+}
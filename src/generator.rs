@@ -0,0 +1,122 @@
+//! Generates puzzles which have exactly one solution.
+//!
+//! The difficulty is judged by how much of the grid the rule based solver can figure out on its
+//! own. The more empty cells it has to leave open, the more the player has to reason about laser
+//! paths.
+
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::laser::Ruleset;
+use crate::puzzle::Puzzle;
+use crate::solver;
+use rand::Rng;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Gives up after this many random grids. All atom counts up to 8 usually succeed in far fewer.
+const MAX_ATTEMPTS: usize = 10_000;
+
+/// The most atoms [generate] looks for puzzles with. At 12 only about one in a few hundred random
+/// grids has a unique solution, and with more the search takes minutes.
+pub const MAX_ATOMS: u8 = 12;
+
+/// How much reasoning a puzzle needs beyond the simple rules of the [crate::solver].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Difficulty {
+    /// The rules leave fewer than 20 empty cells open.
+    Easy,
+    /// The rules leave 20 to 27 empty cells open.
+    Medium,
+    /// The rules leave more empty cells open.
+    Hard,
+}
+
+impl Difficulty {
+    /// Rates a puzzle by the number of empty cells the rule based solver can not rule out.
+    pub fn of(puzzle: &Puzzle) -> Difficulty {
+        let knowledge = solver::solve_as_much_as_you_can(&puzzle.observations);
+        let undecided = GRID_SIZE * GRID_SIZE - knowledge.empty_cells().count();
+        // Thresholds are picked so that most random 5 atom puzzles end up as medium.
+        match undecided.saturating_sub(puzzle.atom_count as usize) {
+            0..=19 => Difficulty::Easy,
+            20..=27 => Difficulty::Medium,
+            _ => Difficulty::Hard,
+        }
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difficulty::Easy => f.write_str("easy"),
+            Difficulty::Medium => f.write_str("medium"),
+            Difficulty::Hard => f.write_str("hard"),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!(
+                "unknown difficulty '{}', use easy, medium or hard",
+                s
+            )),
+        }
+    }
+}
+
+/// Generates a random puzzle with a unique solution and the requested difficulty. Returns the
+/// hidden grid together with the puzzle, or None if no such puzzle was found or there are more
+/// than [MAX_ATOMS] atoms.
+pub fn generate<R: Rng>(
+    atom_count: u8,
+    ruleset: Ruleset,
+    difficulty: Option<Difficulty>,
+    rng: &mut R,
+) -> Option<(AtomGrid, Puzzle)> {
+    if atom_count > MAX_ATOMS {
+        return None;
+    }
+    for _ in 0..MAX_ATTEMPTS {
+        let grid = AtomGrid::random_with(atom_count, rng);
+        let puzzle = Puzzle::from_grid(&grid, atom_count, ruleset);
+        if difficulty.is_some_and(|d| d != Difficulty::of(&puzzle)) {
+            continue;
+        }
+        if puzzle.is_unique() {
+            return Some((grid, puzzle));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brute_force;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn generated_puzzles_are_unique_and_reproducible() {
+        let (grid, puzzle) =
+            generate(4, Ruleset::Classic, None, &mut StdRng::seed_from_u64(7)).expect("puzzle");
+        assert_eq!(brute_force::find_solutions(&puzzle, 2), vec![grid]);
+
+        let (again, _) =
+            generate(4, Ruleset::Classic, None, &mut StdRng::seed_from_u64(7)).expect("puzzle");
+        assert_eq!(grid, again);
+    }
+
+    #[test]
+    fn too_many_atoms_are_refused_at_once() {
+        let mut rng = StdRng::seed_from_u64(1);
+        assert!(generate(MAX_ATOMS + 1, Ruleset::Classic, None, &mut rng).is_none());
+        assert!(generate(64, Ruleset::Classic, None, &mut rng).is_none());
+    }
+}
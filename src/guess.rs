@@ -0,0 +1,271 @@
+//! Checking a guess: where its lasers would come out differently from what was observed, and
+//! whether other atoms than the hidden ones are still a solution. Whether such atoms count as
+//! right is up to the [Grading].
+
+use crate::atom_grid::AtomGrid;
+use crate::laser::{side_name, Direction, LaserPath, LaserTip, Ruleset};
+use crate::observation::{Observations, ProbeResult};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Which guesses are right. Several grids can give the same observations, and a player who finds
+/// one of the others has no way to tell it apart from the hidden one.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Grading {
+    /// Only the hidden atoms are right.
+    #[default]
+    Exact,
+    /// Any atoms are right which are as many as the hidden ones and give the same observations
+    /// when probed from all sides.
+    Observations,
+}
+
+impl Display for Grading {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Grading::Exact => f.write_str("exact"),
+            Grading::Observations => f.write_str("observations"),
+        }
+    }
+}
+
+impl FromStr for Grading {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exact" => Ok(Grading::Exact),
+            "observations" => Ok(Grading::Observations),
+            _ => Err(format!(
+                "unknown grading '{}', use exact or observations",
+                s
+            )),
+        }
+    }
+}
+
+/// Whether the guess is right. Unlike [check], this compares with the whole puzzle, not just the
+/// probes made so far.
+pub fn grade(guess: &AtomGrid, hidden: &AtomGrid, ruleset: Ruleset, grading: Grading) -> bool {
+    match grading {
+        Grading::Exact => guess == hidden,
+        Grading::Observations => {
+            guess.atom_count() == hidden.atom_count()
+                && Observations::observe_all_fast(hidden, ruleset).is_explained_by(guess)
+        }
+    }
+}
+
+/// What a guess amounts to.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Verdict {
+    /// The hidden atoms.
+    Correct,
+    /// Other atoms, but as many and giving the same observations, so the player can't tell them
+    /// apart from the hidden ones.
+    Alternative,
+    /// The atoms give the same observations, but there are `guessed` of them instead of `hidden`.
+    #[allow(missing_docs)]
+    AtomCount { guessed: u32, hidden: u32 },
+    /// The observations differ, see [GuessCheck::differences].
+    Wrong,
+}
+
+/// A probed position where the guess gives another result than the hidden atoms.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Difference {
+    /// The row or column of the position, as for [LaserTip::new].
+    pub shift: u8,
+    /// The direction of the laser shone in from the position.
+    pub direction: Direction,
+    /// What was observed.
+    pub observed: ProbeResult,
+    /// What the laser does with the guessed atoms.
+    pub guessed: ProbeResult,
+    /// The way the laser takes through the guessed atoms, e.g. to draw it with
+    /// [draw_path](crate::laser::draw_path).
+    pub path: LaserPath,
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}: the laser {}, with your atoms it {}",
+            side_name(self.direction),
+            self.shift,
+            describe(self.observed),
+            describe(self.guessed)
+        )
+    }
+}
+
+fn describe(result: ProbeResult) -> String {
+    match result {
+        ProbeResult::Absorbed => "is absorbed".to_owned(),
+        ProbeResult::Reflected => "comes back out".to_owned(),
+        ProbeResult::Exit(shift, direction) => {
+            format!("comes out at {} {}", side_name(direction), shift)
+        }
+    }
+}
+
+/// The result of [check].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GuessCheck {
+    /// What the guess amounts to.
+    pub verdict: Verdict,
+    /// Every probed position where the guess gives another result, in the order of
+    /// [Observations::iter]. Both ends of a laser which comes out are listed.
+    pub differences: Vec<Difference>,
+}
+
+/// Observes the guess from all sides and compares it with the observations of the hidden atoms.
+/// Positions which were not probed yet don't count, so for a game in progress the guess only has
+/// to agree with the probes so far.
+pub fn check(guess: &AtomGrid, hidden: &AtomGrid, observations: &Observations) -> GuessCheck {
+    let ruleset = observations.ruleset;
+    let guessed = Observations::observe_all(guess, ruleset);
+    let differences: Vec<Difference> = observations
+        .iter()
+        .into_iter()
+        .filter_map(|(direction, shift, _)| {
+            let observed = observations.result(shift, direction)?;
+            let guessed = guessed.result(shift, direction)?;
+            (observed != guessed).then(|| Difference {
+                shift,
+                direction,
+                observed,
+                guessed,
+                path: LaserTip::new(shift, direction).record_path(guess, ruleset),
+            })
+        })
+        .collect();
+
+    let verdict = if guess == hidden {
+        Verdict::Correct
+    } else if !differences.is_empty() {
+        Verdict::Wrong
+    } else if guess.atom_count() != hidden.atom_count() {
+        Verdict::AtomCount {
+            guessed: guess.atom_count(),
+            hidden: hidden.atom_count(),
+        }
+    } else {
+        Verdict::Alternative
+    };
+    GuessCheck {
+        verdict,
+        differences,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brute_force;
+    use crate::i8vec2::I8Vec2;
+    use crate::laser::Direction::*;
+    use crate::puzzle::Puzzle;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn wrong_guesses_are_explained() {
+        let hidden = AtomGrid::from_bitboard(35184640598018);
+        let observations = Observations::observe_all(&hidden, Ruleset::Classic);
+        assert_eq!(
+            check(&hidden, &hidden, &observations),
+            GuessCheck {
+                verdict: Verdict::Correct,
+                differences: vec![],
+            }
+        );
+
+        let checked = check(&AtomGrid::default(), &hidden, &observations);
+        assert_eq!(checked.verdict, Verdict::Wrong);
+        assert!(!checked.differences.is_empty());
+        for difference in &checked.differences {
+            // Without atoms every laser goes straight through.
+            assert_eq!(
+                difference.guessed,
+                ProbeResult::Exit(difference.shift, difference.direction.flip())
+            );
+            assert_eq!(difference.path.tips.len(), 10);
+        }
+        let absorbed = checked
+            .differences
+            .iter()
+            .find(|difference| difference.observed == ProbeResult::Absorbed)
+            .unwrap();
+        let message = absorbed.to_string();
+        assert!(message.contains("the laser is absorbed, with your atoms it comes out at"));
+    }
+
+    #[test]
+    fn alternative_solutions_are_recognised() {
+        // Find a grid whose puzzle has another solution.
+        let mut rng = StdRng::seed_from_u64(1);
+        let (hidden, puzzle, other) = loop {
+            let hidden = AtomGrid::random_with(5, &mut rng);
+            let puzzle = Puzzle::from_grid(&hidden, 5, Ruleset::Classic);
+            let solutions = brute_force::find_solutions(&puzzle, 2);
+            if let Some(&other) = solutions.iter().find(|&&grid| grid != hidden) {
+                break (hidden, puzzle, other);
+            }
+        };
+        let checked = check(&other, &hidden, &puzzle.observations);
+        assert_eq!(checked.verdict, Verdict::Alternative);
+        assert!(checked.differences.is_empty());
+
+        assert!(!grade(&other, &hidden, Ruleset::Classic, Grading::Exact));
+        assert!(grade(
+            &other,
+            &hidden,
+            Ruleset::Classic,
+            Grading::Observations
+        ));
+        for grading in [Grading::Exact, Grading::Observations] {
+            assert!(grade(&hidden, &hidden, Ruleset::Classic, grading));
+            assert_eq!(grading.to_string().parse(), Ok(grading));
+        }
+        // An alternative to the probes so far is not necessarily one to the whole puzzle.
+        let mut single = AtomGrid::default();
+        single.set(I8Vec2::new(3, 3), true);
+        let mut elsewhere = AtomGrid::default();
+        elsewhere.set(I8Vec2::new(5, 5), true);
+        assert!(!grade(
+            &elsewhere,
+            &single,
+            Ruleset::Classic,
+            Grading::Observations
+        ));
+    }
+
+    #[test]
+    fn only_probed_positions_count() {
+        let atom = |x, y| {
+            let mut grid = AtomGrid::default();
+            grid.set(I8Vec2::new(x, y), true);
+            grid
+        };
+        let hidden = atom(3, 3);
+        let mut observations = Observations::new(Ruleset::Classic);
+        observations.probe(LaserTip::new(0, Right), &hidden);
+
+        // Row 0 is far from either atom.
+        let checked = check(&atom(5, 5), &hidden, &observations);
+        assert_eq!(checked.verdict, Verdict::Alternative);
+        let checked = check(&AtomGrid::default(), &hidden, &observations);
+        assert_eq!(
+            checked.verdict,
+            Verdict::AtomCount {
+                guessed: 0,
+                hidden: 1
+            }
+        );
+        let checked = check(&atom(3, 1), &hidden, &observations);
+        assert_eq!(checked.verdict, Verdict::Wrong);
+        assert_eq!(checked.differences.len(), 2);
+    }
+}
@@ -1,12 +1,15 @@
 //! Simple 2D integer vector based on i8.
 
 use crate::atom_grid::GRID_SIZE;
+use rand::Rng;
 use std::ops::{Add, Sub};
 
 /// A simple 2D integer vector based on i8.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct I8Vec2 {
+    /// Column, from the left.
     pub x: i8,
+    /// Row, from the top.
     pub y: i8,
 }
 
@@ -16,13 +19,15 @@ impl I8Vec2 {
         Self { x, y }
     }
 
+    /// Whether the position is inside the box rather than on its border or beyond.
     pub fn in_grid(&self) -> bool {
         self.x >= 0 && self.x < GRID_SIZE as i8 && self.y >= 0 && self.y < GRID_SIZE as i8
     }
 
-    pub fn random() -> Self {
-        let x = rand::random::<usize>() % GRID_SIZE;
-        let y = rand::random::<usize>() % GRID_SIZE;
+    /// A random position inside the grid, drawn from the given source of randomness.
+    pub fn random_with<R: Rng>(rng: &mut R) -> Self {
+        let x = rng.gen_range(0..GRID_SIZE);
+        let y = rng.gen_range(0..GRID_SIZE);
         Self::new(x as i8, y as i8)
     }
 }
@@ -0,0 +1,239 @@
+//! The JSON shapes of puzzles, solver results and saved games, shared by the command line, the
+//! bindings and the server.
+
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::board::Mark;
+use crate::i8vec2::I8Vec2;
+use crate::laser::Direction::{self, Down, Left, Right, Up};
+use crate::laser::Ruleset;
+use crate::observation::Observations;
+use crate::puzzle::Puzzle;
+use crate::session::{GameSession, Move};
+use crate::solver::{GridKnowledge, UncertainGrid};
+use serde_json::{json, Value};
+
+pub use crate::laser::side_name;
+
+/// The observations as four lists of symbols, one per side of the box.
+pub fn sides_json(observations: &Observations) -> Value {
+    let side = |direction: Direction| -> Vec<String> {
+        observations.sides[direction as usize]
+            .iter()
+            .map(|obs| obs.to_string())
+            .collect()
+    };
+    json!({
+        "top": side(Down),
+        "bottom": side(Up),
+        "left": side(Right),
+        "right": side(Left),
+    })
+}
+
+/// The puzzle with its atom count, the sides and its text format.
+pub fn puzzle_json(puzzle: &Puzzle) -> Value {
+    json!({
+        "atoms": puzzle.atom_count,
+        "sides": sides_json(&puzzle.observations),
+        "text": puzzle.to_string(),
+    })
+}
+
+/// One string per row, using `o` for atoms, `.` for empty cells and `?` for unknown ones.
+pub fn knowledge_json(knowledge: &UncertainGrid) -> Value {
+    let rows: Vec<String> = (0..GRID_SIZE)
+        .map(|y| {
+            (0..GRID_SIZE)
+                .map(|x| match knowledge.get(I8Vec2::new(x as i8, y as i8)) {
+                    GridKnowledge::Atom => 'o',
+                    GridKnowledge::Empty => '.',
+                    GridKnowledge::Unknown => '?',
+                })
+                .collect()
+        })
+        .collect();
+    json!(rows)
+}
+
+/// The cells with atoms as a list of `[x, y]`, row by row.
+pub fn atoms_json(grid: &AtomGrid) -> Value {
+    grid.atoms().map(|v| json!([v.x, v.y])).collect()
+}
+
+/// Reads a list of cells like `[[0, 1], [5, 3]]` as the atoms of a grid.
+pub fn parse_atoms(json: &Value) -> Result<AtomGrid, String> {
+    let error = || format!("'{}' is not a list of cells like [[0, 1], [5, 3]]", json);
+    let mut grid = AtomGrid::default();
+    for cell in json.as_array().ok_or_else(error)? {
+        let (x, y) = match cell.as_array().map(Vec::as_slice) {
+            Some([x, y]) => (x.as_i64(), y.as_i64()),
+            _ => return Err(error()),
+        };
+        let (x, y) = x.zip(y).ok_or_else(error)?;
+        let size = GRID_SIZE as i64;
+        if !(0..size).contains(&x) || !(0..size).contains(&y) {
+            return Err(format!("cell {},{} is outside the box", x, y));
+        }
+        grid.set(I8Vec2::new(x as i8, y as i8), true);
+    }
+    Ok(grid)
+}
+
+/// The direction of a laser shone in from the named side.
+pub fn parse_side(side: &str) -> Result<Direction, String> {
+    match side {
+        "top" => Ok(Down),
+        "bottom" => Ok(Up),
+        "left" => Ok(Right),
+        "right" => Ok(Left),
+        _ => Err(format!(
+            "unknown side '{}', use top, bottom, left or right",
+            side
+        )),
+    }
+}
+
+/// A saved game: the rules, the hidden atoms and the moves, e.g.
+/// `{"move": "probe", "side": "left", "shift": 2}`, `{"move": "mark", "cell": [3, 4], "mark":
+/// "atom"}` (`"empty"`, `"maybe"`, or `null` to clear), `{"move": "note", "side": "top",
+/// "shift": 0, "note": "text"}` (`null` to clear), `{"move": "undo"}`, `{"move": "redo"}` and
+/// `{"move": "guess", "atoms": [[0, 1], ...]}`. It holds the solution, so keep it from the player.
+pub fn session_json(session: &GameSession) -> Value {
+    let moves: Vec<Value> = session
+        .log()
+        .iter()
+        .map(|m| match m {
+            Move::Probe(shift, direction) => {
+                json!({ "move": "probe", "side": side_name(*direction), "shift": shift })
+            }
+            Move::Mark(cell, mark) => json!({
+                "move": "mark",
+                "cell": [cell.x, cell.y],
+                "mark": mark.map(|mark| match mark {
+                    Mark::Atom => "atom",
+                    Mark::Empty => "empty",
+                    Mark::Maybe => "maybe",
+                }),
+            }),
+            Move::Note(shift, direction, note) => json!({
+                "move": "note",
+                "side": side_name(*direction),
+                "shift": shift,
+                "note": note,
+            }),
+            Move::Undo => json!({ "move": "undo" }),
+            Move::Redo => json!({ "move": "redo" }),
+            Move::Guess(guess) => json!({ "move": "guess", "atoms": atoms_json(guess) }),
+        })
+        .collect();
+    json!({
+        "rules": session.observations().ruleset.to_string(),
+        "atoms": atoms_json(session.grid()),
+        "moves": moves,
+    })
+}
+
+/// Reads a game saved by [session_json] and replays its moves.
+pub fn parse_session(json: &Value) -> Result<GameSession, String> {
+    let ruleset: Ruleset = json["rules"].as_str().ok_or("missing rules")?.parse()?;
+    let grid = parse_atoms(&json["atoms"])?;
+    let moves = json["moves"].as_array().ok_or("missing moves")?;
+    let moves = moves
+        .iter()
+        .map(parse_move)
+        .collect::<Result<Vec<_>, _>>()?;
+    GameSession::replay(grid, ruleset, &moves)
+}
+
+fn parse_move(json: &Value) -> Result<Move, String> {
+    match json["move"].as_str() {
+        Some("probe") => {
+            let (shift, direction) = parse_position(json)?;
+            Ok(Move::Probe(shift, direction))
+        }
+        Some("mark") => {
+            // A cell is a grid with just one atom.
+            let cell = parse_atoms(&json!([json["cell"]]))?.atoms().next().unwrap();
+            let mark = match json["mark"].as_str() {
+                None if json["mark"].is_null() => None,
+                Some("atom") => Some(Mark::Atom),
+                Some("empty") => Some(Mark::Empty),
+                Some("maybe") => Some(Mark::Maybe),
+                _ => return Err(format!("bad mark in {}", json)),
+            };
+            Ok(Move::Mark(cell, mark))
+        }
+        Some("note") => {
+            let (shift, direction) = parse_position(json)?;
+            let note = match &json["note"] {
+                Value::Null => None,
+                Value::String(note) => Some(note.clone()),
+                _ => return Err(format!("bad note in {}", json)),
+            };
+            Ok(Move::Note(shift, direction, note))
+        }
+        Some("undo") => Ok(Move::Undo),
+        Some("redo") => Ok(Move::Redo),
+        Some("guess") => Ok(Move::Guess(parse_atoms(&json["atoms"])?)),
+        _ => Err(format!("unknown move {}", json)),
+    }
+}
+
+/// The `side` and `shift` of a position on the border.
+fn parse_position(json: &Value) -> Result<(u8, Direction), String> {
+    let direction = parse_side(json["side"].as_str().unwrap_or_default())?;
+    let shift = json["shift"]
+        .as_u64()
+        .filter(|&shift| shift < GRID_SIZE as u64);
+    let shift = shift.ok_or_else(|| format!("bad shift in {}", json))?;
+    Ok((shift as u8, direction))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atoms_and_sides_round_trip() {
+        let grid = AtomGrid::from_bitboard(35184640598018);
+        assert_eq!(parse_atoms(&atoms_json(&grid)), Ok(grid));
+        assert!(parse_atoms(&json!([[1, 2], [8, 0]])).is_err());
+        assert!(parse_atoms(&json!([[1, 2, 3]])).is_err());
+        assert!(parse_atoms(&json!([["1", 2]])).is_err());
+        assert!(parse_atoms(&json!({})).is_err());
+        for direction in Direction::all() {
+            assert_eq!(parse_side(side_name(direction)), Ok(direction));
+        }
+        assert!(parse_side("front").is_err());
+    }
+
+    #[test]
+    fn sessions_round_trip() {
+        let grid = AtomGrid::from_bitboard(35184640598018);
+        let mut session = GameSession::new(grid, Ruleset::Plain);
+        session.probe(2, Left).unwrap();
+        session
+            .set_mark(I8Vec2::new(3, 4), Some(Mark::Atom))
+            .unwrap();
+        session
+            .set_mark(I8Vec2::new(3, 4), Some(Mark::Maybe))
+            .unwrap();
+        session.set_note(0, Down, Some("maybe".to_owned())).unwrap();
+        session.undo();
+        session.undo();
+        session.redo();
+        session.guess(grid);
+        let json = session_json(&session);
+        assert_eq!(
+            json["moves"][0],
+            json!({"move": "probe", "side": "right", "shift": 2})
+        );
+        assert_eq!(parse_session(&json), Ok(session));
+
+        let mut broken = json.clone();
+        broken["moves"][1]["mark"] = json!("perhaps");
+        assert!(parse_session(&broken).is_err());
+        broken["moves"] = json!([{"move": "undo"}]);
+        assert!(parse_session(&broken).is_err());
+    }
+}
@@ -1,7 +1,45 @@
+//! The laser rules: how a laser shone into the box moves past the atoms.
+
 use crate::atom_grid::{AtomGrid, GRID_SIZE};
 use crate::i8vec2::I8Vec2;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 use Direction::*;
 
+/// The variant of the game that is played. They only differ in what happens right at the edge.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Ruleset {
+    /// Rules of the original Black Box game: A laser entering next to an atom on the edge of the
+    /// box is reflected immediately. This is what the puzzles always used.
+    #[default]
+    Classic,
+    /// Not part of the original game: the movement rules of [LaserTip::move_once] apply on the
+    /// edge as well. A single atom next to the entry turns the laser away along the outside of the
+    /// box, which is observed as an absorption. Two atoms still reflect the laser.
+    Plain,
+}
+
+impl Display for Ruleset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ruleset::Classic => f.write_str("classic"),
+            Ruleset::Plain => f.write_str("plain"),
+        }
+    }
+}
+
+impl FromStr for Ruleset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(Ruleset::Classic),
+            "plain" => Ok(Ruleset::Plain),
+            _ => Err(format!("unknown ruleset '{}', use classic or plain", s)),
+        }
+    }
+}
+
 /// We simulate the laser moving through the black box step by step. The laser starts at the border
 /// of the box (so at "-1" or "max+1" coordinates). Its movement is only influenced by the three atoms
 /// which may be positioned in front / front-left / front-right.
@@ -16,7 +54,8 @@ pub struct LaserTip {
 }
 
 impl LaserTip {
-    // Creating a new laser at the border of the box with a given shift and direction.
+    /// A laser on the border of the box, about to be shone in. `shift` is the row or column,
+    /// counted from the top left, and `direction` the way it moves into the box.
     pub fn new(shift: u8, direction: Direction) -> Self {
         match direction {
             Up => LaserTip {
@@ -54,6 +93,7 @@ impl LaserTip {
         }
     }
 
+    /// The laser one cell further ahead.
     pub fn forward(self) -> Self {
         LaserTip {
             position: self.position + self.direction.dxy(),
@@ -61,12 +101,19 @@ impl LaserTip {
         }
     }
 
+    /// The cell the laser is in, `-1` or [GRID_SIZE] on the border.
     pub fn position(self) -> I8Vec2 {
         self.position
     }
 
+    /// The direction the laser moves in.
+    pub fn direction(self) -> Direction {
+        self.direction
+    }
+
     /// Creates a new laser tip following the movement rules on the given atom grid.
     ///
+    /// ```text
     /// Rule 1: If there is an atom in front, be absorbed.
     ///  * o *
     ///  . ↑ .
@@ -86,6 +133,7 @@ impl LaserTip {
     ///  o . .
     ///  . ↑ →
     ///  . . .
+    /// ```
     pub fn move_once(self, grid: &AtomGrid) -> Option<Self> {
         // Rule 1. Afterward we can assume front == false.
         let front = self.position + self.direction.dxy();
@@ -127,38 +175,219 @@ impl LaserTip {
         unreachable!("Logic error in laser movement. Movement rules not fully defined.")
     }
 
-    pub fn traverse_grid(self, grid: &AtomGrid) -> (Option<Self>, u8) {
-        let mut laser = self;
-        for move_count in 1..=u8::MAX {
-            let l = laser.move_once(grid);
+    /// Moves the laser until it leaves the grid, is absorbed or runs in circles.
+    pub fn traverse_grid(self, grid: &AtomGrid, ruleset: Ruleset) -> TraversalOutcome {
+        self.walk(grid, ruleset, |_| {})
+    }
 
-            if let Some(l) = l {
-                if !l.position.in_grid() {
-                    return (Some(l), move_count);
+    /// Like [LaserTip::traverse_grid], but records every laser tip visited on the way.
+    pub fn record_path(self, grid: &AtomGrid, ruleset: Ruleset) -> LaserPath {
+        let mut tips = vec![self];
+        let outcome = self.walk(grid, ruleset, |l| tips.push(l));
+        LaserPath { tips, outcome }
+    }
+
+    /// Moves the laser until it leaves the grid, is absorbed or runs in circles. Every new laser
+    /// tip is handed to `visit`, including the one outside the grid.
+    fn walk(
+        self,
+        grid: &AtomGrid,
+        ruleset: Ruleset,
+        mut visit: impl FnMut(Self),
+    ) -> TraversalOutcome {
+        // One bitboard of visited positions per direction. Only positions inside the grid can
+        // ever be revisited, as the walk ends as soon as the laser leaves.
+        let mut visited = [0u64; 4];
+        let mut laser = self;
+        let mut moves = 0;
+        loop {
+            moves += 1;
+            let Some(l) = laser.move_once(grid) else {
+                return TraversalOutcome::Absorbed { moves };
+            };
+            if moves == 1 && !self.position.in_grid() && l.direction != self.direction {
+                // Turned before even entering the box.
+                if ruleset == Ruleset::Classic || l.direction == self.direction.flip() {
+                    let l = LaserTip {
+                        position: self.position,
+                        direction: self.direction.flip(),
+                    };
+                    visit(l);
+                    return TraversalOutcome::Exited { tip: l, moves };
                 } else {
-                    laser = l;
+                    visit(l);
+                    return TraversalOutcome::Missed { moves };
                 }
-            } else {
-                return (None, move_count);
             }
+            visit(l);
+            if !l.position.in_grid() {
+                return TraversalOutcome::Exited { tip: l, moves };
+            }
+            let bit = 1 << (l.position.y as usize * GRID_SIZE + l.position.x as usize);
+            if visited[l.direction as usize] & bit != 0 {
+                return TraversalOutcome::Loop { moves };
+            }
+            visited[l.direction as usize] |= bit;
+            laser = l;
         }
-        panic!("Laser did not leave the grid after 255 moves. Infinite loop detected.");
     }
 }
 
+/// How a laser traversal ended. Moves are counted including the last one, i.e. the move into the
+/// atom for an absorption.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[allow(missing_docs)] // The fields are explained here and on the variants.
+pub enum TraversalOutcome {
+    /// The laser left the grid, `tip` is the laser on the border outside.
+    Exited { tip: LaserTip, moves: usize },
+    /// The laser hit an atom head on.
+    Absorbed { moves: usize },
+    /// The laser was turned away by an atom on the edge and travels along the outside of the box.
+    /// Only happens with [Ruleset::Plain], the classic rules reflect it instead.
+    Missed { moves: usize },
+    /// The laser came back to a position and direction it already had, so it never leaves.
+    /// This can't happen for lasers shone in from the border with the current rules.
+    Loop { moves: usize },
+}
+
+impl TraversalOutcome {
+    /// The laser tip outside the grid, if it came out at all.
+    pub fn exit(self) -> Option<LaserTip> {
+        match self {
+            TraversalOutcome::Exited { tip, .. } => Some(tip),
+            _ => None,
+        }
+    }
+
+    /// The number of moves until the laser came out or stopped.
+    pub fn moves(self) -> usize {
+        match self {
+            TraversalOutcome::Exited { moves, .. }
+            | TraversalOutcome::Absorbed { moves }
+            | TraversalOutcome::Missed { moves }
+            | TraversalOutcome::Loop { moves } => moves,
+        }
+    }
+}
+
+/// The way a laser took through the grid, used to explain observations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LaserPath {
+    /// All laser tips in order, starting with the one on the border where the laser was shone in.
+    pub tips: Vec<LaserTip>,
+    /// How the traversal ended.
+    pub outcome: TraversalOutcome,
+}
+
+/// What happened to the laser at a single tip of the path.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PathStep {
+    /// Passed the cell unhindered, in this direction.
+    Straight(Direction),
+    /// Deflected by a single atom on the corner.
+    Turn,
+    /// Sent back by atoms on both corners.
+    Reflection,
+    /// The laser hit an atom right in front.
+    Absorption,
+}
+
+impl LaserPath {
+    /// Classifies every tip by comparing it with the next one.
+    pub fn steps(&self) -> Vec<(I8Vec2, PathStep)> {
+        let mut steps: Vec<(I8Vec2, PathStep)> = self
+            .tips
+            .windows(2)
+            .map(|w| {
+                let step = if w[1].direction == w[0].direction {
+                    PathStep::Straight(w[0].direction)
+                } else if w[1].direction == w[0].direction.flip() {
+                    PathStep::Reflection
+                } else {
+                    PathStep::Turn
+                };
+                (w[0].position, step)
+            })
+            .collect();
+        let last = self.tips[self.tips.len() - 1];
+        if let TraversalOutcome::Absorbed { .. } = self.outcome {
+            steps.push((last.position, PathStep::Absorption));
+        } else {
+            steps.push((last.position, PathStep::Straight(last.direction)));
+        }
+        steps
+    }
+}
+
+/// Draws the path on top of the grid, including the border the laser enters and leaves through.
+///
+/// ```text
+/// ↑ → ↓ ←  laser moving straight
+///    +     turned by an atom on the corner
+///    *     reflected by atoms on both corners
+///    ×     absorbed by the atom in front
+/// ```
+pub fn draw_path(grid: &AtomGrid, path: &LaserPath) -> Result<String, std::fmt::Error> {
+    use std::fmt::Write;
+
+    const SIZE: usize = GRID_SIZE + 2;
+    let mut cells = [[' '; SIZE]; SIZE];
+    for (y, row) in cells.iter_mut().enumerate().take(GRID_SIZE + 1).skip(1) {
+        for (x, cell) in row.iter_mut().enumerate().take(GRID_SIZE + 1).skip(1) {
+            let v = I8Vec2::new(x as i8 - 1, y as i8 - 1);
+            *cell = if grid.get(v) { 'o' } else { '.' };
+        }
+    }
+
+    // Later steps overwrite earlier ones, but a straight pass never hides a special step.
+    for (position, step) in path.steps() {
+        let (x, y) = (position.x + 1, position.y + 1);
+        if x < 0 || y < 0 || x >= SIZE as i8 || y >= SIZE as i8 {
+            continue;
+        }
+        let cell = &mut cells[y as usize][x as usize];
+        *cell = match step {
+            PathStep::Straight(direction) if !matches!(*cell, '+' | '*' | '×') => {
+                direction.arrow()
+            }
+            PathStep::Straight(_) => *cell,
+            PathStep::Turn => '+',
+            PathStep::Reflection => '*',
+            PathStep::Absorption => '×',
+        };
+    }
+
+    let mut f = String::new();
+    for row in cells {
+        for cell in row {
+            f.write_char(' ')?;
+            f.write_char(cell)?;
+        }
+        f.write_char('\n')?;
+    }
+    Ok(f)
+}
+
+/// The direction a laser moves in. `Up` is towards the top row.
 #[derive(Copy, Clone, PartialEq, Debug, Eq)]
 pub enum Direction {
+    /// Towards the top row, for lasers shone in from the bottom.
     Up = 0,
+    /// Towards the bottom row, for lasers shone in from the top.
     Down = 1,
+    /// Towards the left column, for lasers shone in from the right.
     Left = 2,
+    /// Towards the right column, for lasers shone in from the left.
     Right = 3,
 }
 
 impl Direction {
+    /// All four directions, in the order of their numbers.
     pub const fn all() -> [Direction; 4] {
         [Up, Down, Left, Right]
     }
 
+    /// The step to the next cell.
     pub fn dxy(self) -> I8Vec2 {
         match self {
             Up => I8Vec2::new(0, -1),
@@ -168,6 +397,7 @@ impl Direction {
         }
     }
 
+    /// Turned by 90° clockwise, with y pointing down as on the screen.
     pub fn clockwise(self) -> Self {
         match self {
             Up => Right,
@@ -177,6 +407,7 @@ impl Direction {
         }
     }
 
+    /// Turned by 90° counter clockwise.
     pub fn counter_clockwise(self) -> Self {
         match self {
             Up => Left,
@@ -186,6 +417,17 @@ impl Direction {
         }
     }
 
+    /// An arrow pointing in this direction, for drawing.
+    pub fn arrow(self) -> char {
+        match self {
+            Up => '↑',
+            Down => '↓',
+            Left => '←',
+            Right => '→',
+        }
+    }
+
+    /// The opposite direction.
     pub fn flip(self) -> Self {
         match self {
             Up => Down,
@@ -196,6 +438,17 @@ impl Direction {
     }
 }
 
+/// The side of the box a laser moving in this direction is shone in from, named like on the
+/// command line.
+pub fn side_name(direction: Direction) -> &'static str {
+    match direction {
+        Down => "top",
+        Up => "bottom",
+        Right => "left",
+        Left => "right",
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -241,42 +494,139 @@ mod tests {
 
         // Restart the laser and let it traverse the grid
         let laser = LaserTip::new(0, Right);
-        let laser = laser.traverse_grid(&grid).0.expect("traversal possible");
+        let laser = laser
+            .traverse_grid(&grid, Ruleset::Classic)
+            .exit()
+            .expect("traversal possible");
         assert_eq!(laser.position, I8Vec2::new(8, 0));
 
         // When shining the laser in the second row, we expect a reflection towards the top
         let laser = LaserTip::new(1, Right);
-        let laser = laser.traverse_grid(&grid).0.expect("traversal possible");
+        let laser = laser
+            .traverse_grid(&grid, Ruleset::Classic)
+            .exit()
+            .expect("traversal possible");
         assert_eq!(laser.position, I8Vec2::new(1, -1));
 
         // Next laser is absorbed
         let laser = LaserTip::new(2, Right);
-        let laser = laser.traverse_grid(&grid);
-        assert_eq!(laser, (None, 3));
+        let laser = laser.traverse_grid(&grid, Ruleset::Classic);
+        assert_eq!(laser, TraversalOutcome::Absorbed { moves: 3 });
 
         // Next laser leaves the grid on the left side, but further down. It was reflected twice.
         let laser = LaserTip::new(3, Right);
-        let laser = laser.traverse_grid(&grid).0.expect("traversal possible");
+        let laser = laser
+            .traverse_grid(&grid, Ruleset::Classic)
+            .exit()
+            .expect("traversal possible");
         assert_eq!(laser.position, I8Vec2::new(-1, 5));
 
         // On y=4 the laser is absorbed again
         let laser = LaserTip::new(4, Right);
-        let laser = laser.traverse_grid(&grid);
-        assert_eq!(laser, (None, 4));
+        let laser = laser.traverse_grid(&grid, Ruleset::Classic);
+        assert_eq!(laser, TraversalOutcome::Absorbed { moves: 4 });
 
         // For y=5 the laser comes back to y=3 by symmetry
         let laser = LaserTip::new(5, Right);
-        let laser = laser.traverse_grid(&grid).0.expect("traversal possible");
+        let laser = laser
+            .traverse_grid(&grid, Ruleset::Classic)
+            .exit()
+            .expect("traversal possible");
         assert_eq!(laser.position, I8Vec2::new(-1, 3));
 
         // For y=6 the laser is absorbed again
         let laser = LaserTip::new(6, Right);
-        let laser = laser.traverse_grid(&grid);
-        assert_eq!(laser, (None, 3));
+        let laser = laser.traverse_grid(&grid, Ruleset::Classic);
+        assert_eq!(laser, TraversalOutcome::Absorbed { moves: 3 });
 
         // For y=7 the laser is reflected down.
         let laser = LaserTip::new(7, Right);
-        let laser = laser.traverse_grid(&grid).0.expect("traversal possible");
+        let laser = laser
+            .traverse_grid(&grid, Ruleset::Classic)
+            .exit()
+            .expect("traversal possible");
         assert_eq!(laser.position, I8Vec2::new(1, 8));
     }
+
+    #[test]
+    fn test_recorded_path() {
+        let grid = AtomGrid::from_bitboard(35184640598018);
+
+        for direction in Direction::all() {
+            for shift in 0..GRID_SIZE as u8 {
+                let laser = LaserTip::new(shift, direction);
+                let outcome = laser.traverse_grid(&grid, Ruleset::Classic);
+                let path = laser.record_path(&grid, Ruleset::Classic);
+                assert_eq!(path.outcome, outcome);
+                match outcome {
+                    TraversalOutcome::Exited { tip, moves } => {
+                        assert_eq!(path.tips.len(), moves + 1);
+                        assert_eq!(path.tips.last(), Some(&tip));
+                    }
+                    outcome => assert_eq!(path.tips.len(), outcome.moves()),
+                }
+            }
+        }
+
+        // Reflected twice on the way from left 3 to left 5, see test_laser_path.
+        let path = LaserTip::new(3, Right).record_path(&grid, Ruleset::Classic);
+        let turns = path
+            .steps()
+            .iter()
+            .filter(|(_, step)| *step == PathStep::Turn)
+            .count();
+        assert_eq!(turns, 2);
+
+        let drawing = draw_path(&grid, &path).expect("drawing works");
+        println!("{}", drawing);
+        let rows: Vec<&str> = drawing.lines().collect();
+        assert_eq!(rows.len(), GRID_SIZE + 2);
+        assert_eq!(rows[4], " → → + . . . . . .  ");
+        assert_eq!(rows[5], "   . ↓ . o . . . .  ");
+        assert_eq!(rows[6], " ← ← + . . . . . o  ");
+    }
+
+    #[test]
+    fn test_edge_rules() {
+        // A single atom on the edge, next to the lasers in row 2 and 4.
+        let mut grid = AtomGrid::default();
+        grid.set(I8Vec2::new(0, 3), true);
+
+        for shift in [2, 4] {
+            let laser = LaserTip::new(shift, Right);
+            let classic = laser.traverse_grid(&grid, Ruleset::Classic);
+            assert_eq!(classic.moves(), 1);
+            assert_eq!(
+                classic.exit().and_then(|l| l.deconstruct()),
+                Some((shift, Right))
+            );
+
+            let plain = laser.traverse_grid(&grid, Ruleset::Plain);
+            assert_eq!(plain, TraversalOutcome::Missed { moves: 1 });
+        }
+
+        // Two atoms on the edge reflect in both rulesets.
+        grid.set(I8Vec2::new(0, 5), true);
+        for ruleset in [Ruleset::Classic, Ruleset::Plain] {
+            let laser = LaserTip::new(4, Right).traverse_grid(&grid, ruleset);
+            assert_eq!(laser.exit().and_then(|l| l.deconstruct()), Some((4, Right)));
+        }
+    }
+
+    #[test]
+    fn test_loop_detection() {
+        // Two pairs of atoms reflect the laser back and forth in the column between them.
+        let mut grid = AtomGrid::default();
+        for v in [(0, 0), (2, 0), (0, 4), (2, 4)] {
+            grid.set(I8Vec2::new(v.0, v.1), true);
+        }
+        let laser = LaserTip {
+            position: I8Vec2::new(1, 2),
+            direction: Up,
+        };
+        assert_eq!(
+            laser.traverse_grid(&grid, Ruleset::Classic),
+            TraversalOutcome::Loop { moves: 5 }
+        );
+    }
 }
@@ -0,0 +1,51 @@
+#![warn(missing_docs)]
+//! The engine behind the laser puzzles, also known as Black Box: atoms are hidden in an 8x8 box
+//! and lasers shone in from the sides reveal where they are.
+//!
+//! - [atom_grid] holds the atoms and [laser] moves a laser through them.
+//! - [observation] records what comes out on the sides and [puzzle] adds the atom count and the
+//!   text format.
+//! - [solver] deduces what it can with a few rules, [brute_force] and [sat] find all solutions.
+//! - [generator] makes puzzles with exactly one solution.
+//! - [session] keeps track of a game in progress, with undo and a log to replay, and [board]
+//!   holds the player's marks and checks them against the observations. [guess] explains what
+//!   is wrong with a guess.
+//!
+//! ```
+//! use laser_puzzle::atom_grid::AtomGrid;
+//! use laser_puzzle::laser::Ruleset;
+//! use laser_puzzle::{brute_force, puzzle::Puzzle};
+//!
+//! let grid = AtomGrid::from_bitboard(35184640598018);
+//! let puzzle = Puzzle::from_grid(&grid, 5, Ruleset::Classic);
+//! assert_eq!(brute_force::find_solutions(&puzzle, 2), vec![grid]);
+//! ```
+
+pub mod atom_grid;
+mod bitboard;
+pub mod board;
+pub mod booklet;
+pub mod brute_force;
+#[cfg(feature = "capi")]
+pub mod capi;
+mod cdcl;
+pub mod enumeration;
+pub mod generator;
+pub mod guess;
+pub mod i8vec2;
+pub mod json;
+pub mod laser;
+pub mod observation;
+pub mod page;
+#[cfg(test)]
+mod properties;
+pub mod puzzle;
+pub mod sat;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod session;
+pub mod solver;
+pub mod svg;
+pub mod symmetry;
+#[cfg(feature = "wasm")]
+pub mod wasm;
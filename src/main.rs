@@ -1,26 +1,10 @@
-use crate::atom_grid::AtomGrid;
-use crate::observation::Observations;
+use clap::Parser;
 
-mod atom_grid;
-mod i8vec2;
-mod laser;
-mod observation;
-mod solver;
+mod cli;
 
 fn main() {
-    let g = AtomGrid::random(5);
-
-    println!("BitBoard: {}", g.as_bitboard());
-
-    let o = Observations::observe_all(&g);
-    println!(
-        "{}",
-        observation::draw(&g, &o).expect("Failed to draw observation")
-    );
-
-    let s = solver::solve_as_much_as_you_can(&o);
-    println!(
-        "{}",
-        solver::draw(&s, &o).expect("Failed to draw solver state")
-    );
+    if let Err(e) = cli::run(cli::Cli::parse()) {
+        eprintln!("error: {}", e);
+        std::process::exit(2);
+    }
 }
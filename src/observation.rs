@@ -1,31 +1,45 @@
+//! What the player sees: the result of every laser written on the sides of the box.
+
 use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::bitboard::BitboardProbe;
 use crate::i8vec2::I8Vec2;
 use crate::laser::Direction::*;
-use crate::laser::{Direction, LaserTip};
+use crate::laser::{Direction, LaserTip, Ruleset};
 use std::fmt::{Display, Formatter, Write};
 
 /// The observation is the information derived from an atom grid using a laser and available to the
 /// player. It is the player's job to use this information to determine the atom grid.
 ///
 /// We store all the observations in a single struct and add to it after each probe.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Observations {
     next_observation: Observation,
+    /// What was seen on each side, indexed by the direction of a laser shone in from there and
+    /// its shift. So `sides[Down as usize]` is the top side, from left to right.
     pub sides: [[Observation; GRID_SIZE]; 4],
+    /// The rules all probes are made with.
+    pub ruleset: Ruleset,
 }
 
 impl Default for Observations {
     fn default() -> Self {
+        Observations::new(Ruleset::default())
+    }
+}
+
+impl Observations {
+    /// Nothing probed yet.
+    pub fn new(ruleset: Ruleset) -> Self {
         Observations {
             next_observation: Observation(3), // We start at 3 as 0-2 have special significance.
             sides: [[NOT_PROBED; GRID_SIZE]; 4],
+            ruleset,
         }
     }
-}
 
-impl Observations {
-    pub fn observe_all(grid: &AtomGrid) -> Self {
-        let mut this = Observations::default();
+    /// Shines a laser in from every position on the border.
+    pub fn observe_all(grid: &AtomGrid, ruleset: Ruleset) -> Self {
+        let mut this = Observations::new(ruleset);
 
         for direction in Direction::all() {
             for shift in 0..GRID_SIZE {
@@ -38,30 +52,117 @@ impl Observations {
         this
     }
 
-    fn probe(&mut self, laser: LaserTip, grid: &AtomGrid) {
+    /// Same as [Observations::observe_all], but computed on the bitboard. Much faster, which
+    /// matters when checking many grids.
+    pub fn observe_all_fast(grid: &AtomGrid, ruleset: Ruleset) -> Self {
+        let probe = BitboardProbe::new(grid);
+        let mut this = Observations::new(ruleset);
+
+        for direction in Direction::all() {
+            for shift in 0..GRID_SIZE as u8 {
+                if this.sides[direction as usize][shift as usize] == NOT_PROBED {
+                    let result = probe.shoot(shift, direction, ruleset);
+                    this.record(shift, direction, result);
+                }
+            }
+        }
+
+        this
+    }
+
+    /// Builds observations from the raw sides, e.g. after reading them from a file. Letters are
+    /// renamed in reading order, so two observations describing the same pairs compare equal.
+    /// Returns None if a letter does not appear exactly twice.
+    pub fn from_sides(sides: [[Observation; GRID_SIZE]; 4], ruleset: Ruleset) -> Option<Self> {
+        let mut this = Observations::new(ruleset);
+        for direction in Direction::all() {
+            for shift in 0..GRID_SIZE {
+                let obs = sides[direction as usize][shift];
+                if !obs.is_letter() {
+                    this.sides[direction as usize][shift] = obs;
+                } else if this.sides[direction as usize][shift] == NOT_PROBED {
+                    let partners: Vec<(usize, usize)> = (0..4)
+                        .flat_map(|d| (0..GRID_SIZE).map(move |s| (d, s)))
+                        .filter(|&(d, s)| sides[d][s] == obs)
+                        .collect();
+                    if partners.len() != 2 {
+                        return None;
+                    }
+                    for (d, s) in partners {
+                        this.sides[d][s] = this.next_observation;
+                    }
+                    this.next_observation = Observation(this.next_observation.0 + 1);
+                }
+            }
+        }
+        Some(this)
+    }
+
+    /// Checks if the grid would produce all observations made so far. Positions which are not
+    /// probed yet are ignored and letters only need to connect the same positions.
+    pub fn is_explained_by(&self, grid: &AtomGrid) -> bool {
+        let probe = BitboardProbe::new(grid);
+        for direction in Direction::all() {
+            for shift in 0..GRID_SIZE {
+                let obs = self.sides[direction as usize][shift];
+                if obs == NOT_PROBED {
+                    continue;
+                }
+                let matches = match probe.shoot(shift as u8, direction, self.ruleset) {
+                    ProbeResult::Absorbed => obs == LASER_ABSORBED,
+                    ProbeResult::Reflected => obs == LASER_REFLECTED,
+                    ProbeResult::Exit(out_shift, out_direction) => {
+                        obs.is_letter()
+                            && self.sides[out_direction as usize][out_shift as usize] == obs
+                    }
+                };
+                if !matches {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Shines in the laser, which must be on the border, and records the result on both sides.
+    /// Probing a position again changes nothing, the result is already written there.
+    pub fn probe(&mut self, laser: LaserTip, grid: &AtomGrid) {
         let (in_shift, in_direction) = laser
             .deconstruct()
             .expect("Probing should only happen with side-lasers.");
-        let (laser_out, move_count) = laser.traverse_grid(grid);
-
-        if move_count <= 1 && laser_out.is_some() {
-            // Reflection
-            self.sides[in_direction as usize][in_shift as usize] = LASER_REFLECTED;
-        } else if let Some(laser_out) = laser_out {
-            // Laser came out somewhere else
-            let (out_shift, out_direction) = laser_out
-                .deconstruct()
-                .expect("Traversal should return the laser on the border.");
-            if in_direction == out_direction && in_shift == out_shift {
+        if self.sides[in_direction as usize][in_shift as usize] != NOT_PROBED {
+            return;
+        }
+        self.record(in_shift, in_direction, shoot(laser, grid, self.ruleset));
+    }
+
+    fn record(&mut self, in_shift: u8, in_direction: Direction, result: ProbeResult) {
+        match result {
+            ProbeResult::Absorbed => {
+                self.sides[in_direction as usize][in_shift as usize] = LASER_ABSORBED;
+            }
+            ProbeResult::Reflected => {
                 self.sides[in_direction as usize][in_shift as usize] = LASER_REFLECTED;
-            } else {
+            }
+            ProbeResult::Exit(out_shift, out_direction) => {
                 self.sides[in_direction as usize][in_shift as usize] = self.next_observation;
                 self.sides[out_direction as usize][out_shift as usize] = self.next_observation;
                 self.next_observation = Observation(self.next_observation.0 + 1);
             }
-        } else {
-            // Laser absorbed
-            self.sides[in_direction as usize][in_shift as usize] = LASER_ABSORBED;
+        }
+    }
+
+    /// What the laser shone in at this position did, None if it wasn't probed yet.
+    pub fn result(&self, shift: u8, direction: Direction) -> Option<ProbeResult> {
+        match self.sides[direction as usize][shift as usize] {
+            NOT_PROBED => None,
+            LASER_ABSORBED => Some(ProbeResult::Absorbed),
+            LASER_REFLECTED => Some(ProbeResult::Reflected),
+            letter => self
+                .iter()
+                .into_iter()
+                .find(|&(d, s, obs)| obs == letter && (d, s) != (direction, shift))
+                .map(|(d, s, _)| ProbeResult::Exit(s, d)),
         }
     }
 
@@ -78,19 +179,66 @@ impl Observations {
     }
 }
 
+/// Where a laser shone in from the border ends up.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProbeResult {
+    /// The laser never came out.
+    Absorbed,
+    /// The laser came out where it went in.
+    Reflected,
+    /// Laser came out somewhere else, given as the constructor parameters to shine in from there.
+    Exit(u8, Direction),
+}
+
+/// Where the laser comes out. [ProbeResult::Absorbed] if it never does, which includes a laser
+/// running in circles and, with [Ruleset::Plain], one turned away along the outside of the box.
+pub fn shoot(laser: LaserTip, grid: &AtomGrid, ruleset: Ruleset) -> ProbeResult {
+    match laser.traverse_grid(grid, ruleset).exit() {
+        None => ProbeResult::Absorbed,
+        Some(tip) => {
+            let out = tip
+                .deconstruct()
+                .expect("Traversal should return the laser on the border.");
+            if Some(out) == laser.deconstruct() {
+                ProbeResult::Reflected
+            } else {
+                ProbeResult::Exit(out.0, out.1)
+            }
+        }
+    }
+}
+
+/// The symbol on the border: a letter where a laser went in or came out, or one of the special
+/// values.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Hash)]
 pub struct Observation(u8);
 
-pub const NOT_PROBED: Observation = Observation(0); // Special value
-pub const LASER_ABSORBED: Observation = Observation(1); // Special value
-pub const LASER_REFLECTED: Observation = Observation(2); // Special value
+/// No laser was shone in here yet.
+pub const NOT_PROBED: Observation = Observation(0);
+/// The laser never came out, drawn as `×`.
+pub const LASER_ABSORBED: Observation = Observation(1);
+/// The laser came out where it went in, drawn as `⇄`.
+pub const LASER_REFLECTED: Observation = Observation(2);
 
-const ALPHABET: &'static str = "ABCDEFGHKLMNPRSTUVWYZ"; // Exclude some letters
+const ALPHABET: &str = "ABCDEFGHKLMNPRSTUVWYZ"; // Exclude some letters
 
 impl Observation {
     pub(crate) fn is_letter(self) -> bool {
         self.0 >= 3
     }
+
+    /// Reads a symbol as written by the Display implementation. Also accepts `x` for absorption.
+    pub fn from_symbol(symbol: char) -> Option<Self> {
+        match symbol {
+            '?' => Some(NOT_PROBED),
+            '×' | 'x' => Some(LASER_ABSORBED),
+            '⇄' => Some(LASER_REFLECTED),
+            c => ALPHABET
+                .chars()
+                .position(|a| a == c)
+                .map(|i| Observation(i as u8 + 3)),
+        }
+    }
 }
 
 impl Display for Observation {
@@ -105,6 +253,8 @@ impl Display for Observation {
     }
 }
 
+/// The grid with the observations around it, as in the puzzle text format. Atoms are drawn as
+/// `o`.
 pub fn draw(grid: &AtomGrid, observations: &Observations) -> Result<String, std::fmt::Error> {
     let mut f = String::new();
     // first, display the row above with lasers pointing down
@@ -146,8 +296,8 @@ pub fn draw(grid: &AtomGrid, observations: &Observations) -> Result<String, std:
 mod tests {
     use crate::atom_grid::AtomGrid;
     use crate::laser::Direction::*;
-    use crate::laser::LaserTip;
-    use crate::observation::{Observations, LASER_ABSORBED, LASER_REFLECTED};
+    use crate::laser::{LaserTip, Ruleset};
+    use crate::observation::{shoot, Observations, ProbeResult, LASER_ABSORBED, LASER_REFLECTED};
 
     #[test]
     fn observation_after_probing() {
@@ -172,4 +322,34 @@ mod tests {
         assert_eq!(obs[6].0, 3);
         assert_eq!(obs[7].0, 4);
     }
+
+    #[test]
+    fn results_match_the_lasers() {
+        let grid = AtomGrid::from_bitboard(35184640598018);
+        let observations = Observations::observe_all(&grid, Ruleset::Classic);
+        for (direction, shift, _) in observations.iter() {
+            let laser = LaserTip::new(shift, direction);
+            let result = shoot(laser, &grid, Ruleset::Classic);
+            assert_eq!(observations.result(shift, direction), Some(result));
+        }
+        assert_eq!(Observations::default().result(3, Down), None);
+    }
+
+    #[test]
+    fn probing_again_changes_nothing() {
+        let grid = AtomGrid::from_bitboard(54043333103714304);
+        let mut observations = Observations::default();
+        observations.probe(LaserTip::new(7, Right), &grid);
+        let once = observations.clone();
+        // More probes than there are letters, from both ends of the laser.
+        for _ in 0..30 {
+            observations.probe(LaserTip::new(7, Right), &grid);
+        }
+        let (shift, direction) = match observations.result(7, Right) {
+            Some(ProbeResult::Exit(shift, direction)) => (shift, direction),
+            result => panic!("The laser should come out, not {:?}", result),
+        };
+        observations.probe(LaserTip::new(shift, direction), &grid);
+        assert_eq!(observations, once);
+    }
 }
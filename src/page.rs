@@ -0,0 +1,176 @@
+//! A single HTML page for playing a puzzle in the browser, without a server. The player marks
+//! cells as atoms or empty and asks whether the atoms are right. The page only holds a hash of the
+//! solution, so reading the source doesn't give it away.
+
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::i8vec2::I8Vec2;
+use crate::laser::LaserTip;
+use crate::observation::NOT_PROBED;
+use crate::puzzle::Puzzle;
+use crate::svg::escape;
+use serde_json::json;
+use std::fmt::Write;
+
+/// The hash the page checks the marked atoms against: 64 bit FNV-1a of the puzzle text, a `|`
+/// and the bitboard of the atoms in decimal. The puzzle text is the salt, so two puzzles with the
+/// same solution don't share a hash.
+pub fn solution_hash(puzzle: &Puzzle, solution: &AtomGrid) -> u64 {
+    fnv1a(format!("{}|{}", puzzle, solution.as_bitboard()).as_bytes())
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+const STYLE: &str = "\
+body { font-family: sans-serif; text-align: center; }
+table { border-collapse: collapse; margin: 1em auto; }
+td { width: 2.2em; height: 2.2em; text-align: center; font-size: 14pt; padding: 0; }
+td.cell { border: 1px solid #444; cursor: pointer; user-select: none; }
+td.atom { background: #222; color: white; }
+td.atom::after { content: '\\25CF'; }
+td.empty { background: #e8e8e8; }
+#result { min-height: 1.5em; font-weight: bold; }
+";
+
+/// Cells go from unknown to atom to empty and back. The hash is the same as in
+/// [solution_hash], with BigInt for the 64 bit arithmetic.
+const SCRIPT: &str = "\
+const marks = new Array(64).fill(0);
+const classes = ['', 'atom', 'empty'];
+for (const td of document.querySelectorAll('td.cell')) {
+  td.addEventListener('click', () => {
+    const i = Number(td.dataset.index);
+    marks[i] = (marks[i] + 1) % classes.length;
+    td.className = ('cell ' + classes[marks[i]]).trim();
+    document.getElementById('result').textContent = '';
+  });
+}
+function fnv1a(text) {
+  let hash = 0xcbf29ce484222325n;
+  for (const byte of new TextEncoder().encode(text)) {
+    hash = ((hash ^ BigInt(byte)) * 0x100000001b3n) & 0xffffffffffffffffn;
+  }
+  return hash;
+}
+function check() {
+  const result = document.getElementById('result');
+  const atoms = marks.filter(m => m === 1).length;
+  if (atoms !== PUZZLE.atoms) {
+    result.textContent = `You marked ${atoms} atoms, the box holds ${PUZZLE.atoms}.`;
+    return;
+  }
+  let bitboard = 0n;
+  marks.forEach((m, i) => { if (m === 1) bitboard |= 1n << BigInt(63 - i); });
+  const right = fnv1a(PUZZLE.text + '|' + bitboard) === BigInt(PUZZLE.hash);
+  result.textContent = right ? 'Correct, well done!' : 'Not quite, keep looking.';
+}
+function reset() {
+  marks.fill(0);
+  for (const td of document.querySelectorAll('td.cell')) td.className = 'cell';
+  document.getElementById('result').textContent = '';
+}
+";
+
+/// The page for a puzzle, checking against `solution`. The solution should be the only one,
+/// otherwise a player who finds another one is told it is wrong.
+pub fn html(title: &str, puzzle: &Puzzle, solution: &AtomGrid) -> Result<String, std::fmt::Error> {
+    let mut clues = vec![vec![String::new(); GRID_SIZE + 2]; GRID_SIZE + 2];
+    for (direction, shift, obs) in puzzle.observations.iter() {
+        if obs != NOT_PROBED {
+            let v = LaserTip::new(shift, direction).position();
+            clues[(v.y + 1) as usize][(v.x + 1) as usize] = obs.to_string();
+        }
+    }
+
+    let mut f = String::new();
+    writeln!(f, "<!DOCTYPE html>")?;
+    writeln!(f, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(f, "<title>{}</title>", escape(title))?;
+    writeln!(f, "<style>\n{}</style>", STYLE)?;
+    writeln!(f, "</head><body>")?;
+    writeln!(f, "<h1>{}</h1>", escape(title))?;
+    writeln!(
+        f,
+        "<p>Find the {} atoms. Click a cell to mark it as an atom, again to mark it empty.</p>",
+        puzzle.atom_count
+    )?;
+
+    writeln!(f, "<table>")?;
+    for (row, row_clues) in clues.iter().enumerate() {
+        write!(f, "<tr>")?;
+        for (column, clue) in row_clues.iter().enumerate() {
+            let v = I8Vec2::new(column as i8 - 1, row as i8 - 1);
+            if v.in_grid() {
+                let index = v.y as usize * GRID_SIZE + v.x as usize;
+                write!(f, "<td class=\"cell\" data-index=\"{}\"></td>", index)?;
+            } else {
+                write!(f, "<td>{}</td>", escape(clue))?;
+            }
+        }
+        writeln!(f, "</tr>")?;
+    }
+    writeln!(f, "</table>")?;
+    writeln!(
+        f,
+        "<p><button onclick=\"check()\">Check</button> <button onclick=\"reset()\">Reset</button></p>"
+    )?;
+    writeln!(f, "<p id=\"result\"></p>")?;
+
+    // The text goes into a script, where only a closing tag could break out of the string.
+    let data = json!({
+        "atoms": puzzle.atom_count,
+        "text": puzzle.to_string(),
+        "hash": solution_hash(puzzle, solution).to_string(),
+    });
+    writeln!(
+        f,
+        "<script>\nconst PUZZLE = {};\n{}</script>",
+        data.to_string().replace("</", "<\\/"),
+        SCRIPT
+    )?;
+    writeln!(f, "</body></html>")?;
+    Ok(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laser::Ruleset;
+
+    #[test]
+    fn page_hides_the_solution() {
+        let grid = AtomGrid::from_bitboard(35184640598018);
+        let puzzle = Puzzle::from_grid(&grid, 5, Ruleset::Classic);
+        let page = html("Puzzle", &puzzle, &grid).unwrap();
+
+        assert_eq!(
+            page.matches("<td class=\"cell\"").count(),
+            GRID_SIZE * GRID_SIZE
+        );
+        assert!(page.contains(&solution_hash(&puzzle, &grid).to_string()));
+        assert!(!page.contains(&grid.as_bitboard().to_string()));
+        for (_, _, obs) in puzzle.observations.iter() {
+            assert!(page.contains(&format!("<td>{}</td>", obs)));
+        }
+    }
+
+    #[test]
+    fn hash_depends_on_puzzle_and_atoms() {
+        let grid = AtomGrid::from_bitboard(35184640598018);
+        let other = AtomGrid::from_bitboard(35184640598019);
+        let puzzle = Puzzle::from_grid(&grid, 5, Ruleset::Classic);
+        let plain = Puzzle::from_grid(&grid, 5, Ruleset::Plain);
+        assert_eq!(solution_hash(&puzzle, &grid), solution_hash(&puzzle, &grid));
+        assert_ne!(
+            solution_hash(&puzzle, &grid),
+            solution_hash(&puzzle, &other)
+        );
+        assert_ne!(solution_hash(&puzzle, &grid), solution_hash(&plain, &grid));
+        // Published test vectors, the script has to compute the same.
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    }
+}
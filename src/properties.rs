@@ -0,0 +1,158 @@
+//! Invariants of the laser rules, checked as property tests on random grids. Failing grids shrink
+//! to a small bitboard, which is printed in the failure message.
+
+use crate::atom_grid::{arbitrary_grid, AtomGrid, GRID_SIZE};
+use crate::laser::{Direction, LaserTip, Ruleset};
+use crate::observation::{self, Observations, ProbeResult};
+use crate::symmetry::{Symmetry, Transform};
+use proptest::prelude::*;
+
+fn ruleset() -> impl Strategy<Value = Ruleset> {
+    prop_oneof![Just(Ruleset::Classic), Just(Ruleset::Plain)]
+}
+
+fn symmetry() -> impl Strategy<Value = Symmetry> {
+    proptest::sample::select(Symmetry::all().to_vec())
+}
+
+/// Every position a laser can be shone in from.
+fn entries() -> Vec<(u8, Direction)> {
+    Direction::all()
+        .into_iter()
+        .flat_map(|d| (0..GRID_SIZE as u8).map(move |s| (s, d)))
+        .collect()
+}
+
+/// How often each observation appears on the sides.
+fn occurrences(observations: &Observations) -> Vec<(observation::Observation, usize)> {
+    let mut counts: Vec<(observation::Observation, usize)> = vec![];
+    for (_, _, obs) in observations.iter() {
+        match counts.iter_mut().find(|(o, _)| *o == obs) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((obs, 1)),
+        }
+    }
+    counts
+}
+
+proptest! {
+    /// A laser shone in where another one came out, comes out where the first one went in.
+    #[test]
+    fn lasers_are_reversible(grid in arbitrary_grid(0..=12), ruleset in ruleset()) {
+        for (shift, direction) in entries() {
+            let laser = LaserTip::new(shift, direction);
+            let Some(tip) = laser.traverse_grid(&grid, ruleset).exit() else {
+                continue;
+            };
+            let (out_shift, out_direction) = tip.deconstruct().expect("Exits are on the border.");
+            let back = LaserTip::new(out_shift, out_direction).traverse_grid(&grid, ruleset);
+            let back = back.exit().and_then(|tip| tip.deconstruct());
+            prop_assert_eq!(
+                back,
+                Some((shift, direction)),
+                "Shone in from {:?} {} on bitboard {} with {} rules",
+                direction,
+                shift,
+                grid.as_bitboard(),
+                ruleset
+            );
+        }
+    }
+
+    /// The reverse laser passes the same cells in the opposite order.
+    #[test]
+    fn reverse_paths_match(grid in arbitrary_grid(0..=12), ruleset in ruleset()) {
+        for (shift, direction) in entries() {
+            let path = LaserTip::new(shift, direction).record_path(&grid, ruleset);
+            let Some((out_shift, out_direction)) =
+                path.outcome.exit().and_then(|tip| tip.deconstruct())
+            else {
+                continue;
+            };
+            let back = LaserTip::new(out_shift, out_direction).record_path(&grid, ruleset);
+            let inside = |tips: &[LaserTip]| -> Vec<_> {
+                tips.iter().map(|t| t.position()).filter(|v| v.in_grid()).collect()
+            };
+            let mut forward = inside(&path.tips);
+            forward.reverse();
+            prop_assert_eq!(
+                inside(&back.tips),
+                forward,
+                "Shone in from {:?} {} on bitboard {} with {} rules",
+                direction,
+                shift,
+                grid.as_bitboard(),
+                ruleset
+            );
+        }
+    }
+
+    /// Probing one side after the other ends up with the same pairs as observing everything at
+    /// once, and every letter is on exactly two sides.
+    #[test]
+    fn every_letter_appears_twice(
+        grid in arbitrary_grid(0..=12),
+        ruleset in ruleset(),
+        order in Just(entries()).prop_shuffle(),
+    ) {
+        let mut observations = Observations::new(ruleset);
+        for (shift, direction) in order {
+            observations.probe(LaserTip::new(shift, direction), &grid);
+        }
+        for (obs, count) in occurrences(&observations) {
+            if obs.is_letter() {
+                prop_assert_eq!(count, 2, "{} on bitboard {}", obs, grid.as_bitboard());
+            }
+        }
+        let relabeled = Observations::from_sides(observations.sides, ruleset);
+        prop_assert_eq!(relabeled, Some(Observations::observe_all(&grid, ruleset)));
+    }
+
+    /// Probing reports what the laser does.
+    #[test]
+    fn probes_follow_the_laser(grid in arbitrary_grid(0..=12), ruleset in ruleset()) {
+        let observations = Observations::observe_all(&grid, ruleset);
+        for (shift, direction) in entries() {
+            let obs = observations.sides[direction as usize][shift as usize];
+            match observation::shoot(LaserTip::new(shift, direction), &grid, ruleset) {
+                ProbeResult::Absorbed => prop_assert_eq!(obs, observation::LASER_ABSORBED),
+                ProbeResult::Reflected => prop_assert_eq!(obs, observation::LASER_REFLECTED),
+                ProbeResult::Exit(out_shift, out_direction) => {
+                    prop_assert!(obs.is_letter());
+                    prop_assert_eq!(
+                        observations.sides[out_direction as usize][out_shift as usize],
+                        obs
+                    );
+                }
+            }
+        }
+    }
+
+    /// Rotating or mirroring the grid rotates or mirrors the observations.
+    #[test]
+    fn observations_are_symmetric(
+        grid in arbitrary_grid(0..=12),
+        ruleset in ruleset(),
+        symmetry in symmetry(),
+    ) {
+        prop_assert_eq!(
+            Observations::observe_all(&grid.transformed(symmetry), ruleset),
+            Observations::observe_all(&grid, ruleset).transformed(symmetry),
+            "{} on bitboard {}",
+            symmetry,
+            grid.as_bitboard()
+        );
+    }
+}
+
+/// An empty box lets every laser straight through.
+#[test]
+fn empty_box() {
+    let observations = Observations::observe_all(&AtomGrid::default(), Ruleset::Classic);
+    for (shift, direction) in entries() {
+        let obs = observations.sides[direction as usize][shift as usize];
+        let opposite = observations.sides[direction.flip() as usize][shift as usize];
+        assert!(obs.is_letter());
+        assert_eq!(obs, opposite);
+    }
+}
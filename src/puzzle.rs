@@ -0,0 +1,278 @@
+//! A puzzle is what we hand to the player: the observations around the box and the number of
+//! atoms hidden inside. This module also defines the text format we use to store puzzles.
+//!
+//! ```text
+//! atoms: 5
+//!    × ⇄ A × B × × C
+//!  ⇄ ? ? ? ? ? ? ? ? ⇄
+//!  ...
+//!    × C × × ⇄ × B ×
+//! ```
+//!
+//! The frame is exactly what `observation::draw` and `solver::draw` print. The cells are ignored,
+//! except that the atom count may be left out when the cells show the atoms (`o`). Puzzles for
+//! other rules than the classic ones have an additional header line, e.g. `rules: plain`.
+
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::laser::Direction::{Down, Left, Right, Up};
+use crate::laser::Ruleset;
+use crate::observation::{Observation, Observations, NOT_PROBED};
+use crate::sat;
+use crate::solver;
+use crate::solver::UncertainGrid;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The observations of a hidden grid and how many atoms it has.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Puzzle {
+    /// Number of hidden atoms.
+    pub atom_count: u8,
+    /// What the player sees on the sides.
+    pub observations: Observations,
+}
+
+impl Puzzle {
+    /// The fully probed puzzle for a hidden grid.
+    pub fn from_grid(grid: &AtomGrid, atom_count: u8, ruleset: Ruleset) -> Self {
+        Puzzle {
+            atom_count,
+            observations: Observations::observe_all(grid, ruleset),
+        }
+    }
+
+    /// Whether exactly one grid explains the puzzle. Asks the [sat] solver, which stays fast for
+    /// many atoms where [brute_force::is_unique](crate::brute_force::is_unique) takes minutes.
+    pub fn is_unique(&self) -> bool {
+        sat::find_solutions(self, 2).len() == 1
+    }
+}
+
+impl Display for Puzzle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "atoms: {}", self.atom_count)?;
+        if self.observations.ruleset != Ruleset::Classic {
+            writeln!(f, "rules: {}", self.observations.ruleset)?;
+        }
+        f.write_str(&solver::draw(
+            &UncertainGrid::default(),
+            &self.observations,
+        )?)
+    }
+}
+
+/// Why a text is not a puzzle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsePuzzleError {
+    /// There is no `atoms:` header and the cells don't show any atoms either.
+    MissingAtomCount,
+    /// The `atoms:` header has something else than a number.
+    InvalidAtomCount(String),
+    /// The `rules:` header names unknown rules.
+    InvalidRuleset(String),
+    /// The frame must have one line above, one below and one for each row.
+    WrongLineCount(usize),
+    /// A line of the frame has the wrong number of symbols.
+    WrongTokenCount {
+        /// The line, counted from 1.
+        line: usize,
+        /// The number of symbols on it.
+        found: usize,
+    },
+    /// A symbol which is neither an observation nor a cell.
+    UnknownSymbol {
+        /// The line, counted from 1.
+        line: usize,
+        /// What was found there.
+        symbol: String,
+    },
+    /// Every letter must appear exactly twice on the border.
+    UnpairedLetter,
+}
+
+impl Display for ParsePuzzleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParsePuzzleError::MissingAtomCount => {
+                f.write_str("missing 'atoms: <count>' header and no atoms in the grid")
+            }
+            ParsePuzzleError::InvalidAtomCount(s) => write!(f, "invalid atom count '{}'", s),
+            ParsePuzzleError::InvalidRuleset(s) => f.write_str(s),
+            ParsePuzzleError::WrongLineCount(n) => {
+                write!(f, "expected {} lines, found {}", GRID_SIZE + 2, n)
+            }
+            ParsePuzzleError::WrongTokenCount { line, found } => {
+                write!(f, "line {}: unexpected number of symbols ({})", line, found)
+            }
+            ParsePuzzleError::UnknownSymbol { line, symbol } => {
+                write!(f, "line {}: unknown symbol '{}'", line, symbol)
+            }
+            ParsePuzzleError::UnpairedLetter => {
+                f.write_str("every letter must appear exactly twice")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParsePuzzleError {}
+
+fn parse_observation(token: &str, line: usize) -> Result<Observation, ParsePuzzleError> {
+    let mut chars = token.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Observation::from_symbol(c),
+        _ => None,
+    }
+    .ok_or_else(|| ParsePuzzleError::UnknownSymbol {
+        line,
+        symbol: token.to_owned(),
+    })
+}
+
+impl FromStr for Puzzle {
+    type Err = ParsePuzzleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines: Vec<(usize, &str)> = s
+            .lines()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l.trim()))
+            .filter(|(_, l)| !l.is_empty())
+            .collect();
+
+        let mut atom_count = None;
+        let mut ruleset = Ruleset::Classic;
+        while let Some(&(_, header)) = lines.first() {
+            if let Some(count) = header.strip_prefix("atoms:") {
+                let count = count.trim();
+                atom_count = Some(
+                    count
+                        .parse::<u8>()
+                        .map_err(|_| ParsePuzzleError::InvalidAtomCount(count.to_owned()))?,
+                );
+            } else if let Some(rules) = header.strip_prefix("rules:") {
+                ruleset = rules
+                    .trim()
+                    .parse()
+                    .map_err(ParsePuzzleError::InvalidRuleset)?;
+            } else {
+                break;
+            }
+            lines.remove(0);
+        }
+
+        if lines.len() != GRID_SIZE + 2 {
+            return Err(ParsePuzzleError::WrongLineCount(lines.len()));
+        }
+
+        let mut sides = [[NOT_PROBED; GRID_SIZE]; 4];
+        let mut atoms_in_cells = 0;
+        for (row, &(line, text)) in lines.iter().enumerate() {
+            let tokens: Vec<&str> = text.split_whitespace().collect();
+            if row == 0 || row == GRID_SIZE + 1 {
+                if tokens.len() != GRID_SIZE {
+                    return Err(ParsePuzzleError::WrongTokenCount {
+                        line,
+                        found: tokens.len(),
+                    });
+                }
+                let direction = if row == 0 { Down } else { Up };
+                for (shift, token) in tokens.iter().enumerate() {
+                    sides[direction as usize][shift] = parse_observation(token, line)?;
+                }
+            } else {
+                if tokens.len() != GRID_SIZE + 2 {
+                    return Err(ParsePuzzleError::WrongTokenCount {
+                        line,
+                        found: tokens.len(),
+                    });
+                }
+                sides[Right as usize][row - 1] = parse_observation(tokens[0], line)?;
+                sides[Left as usize][row - 1] = parse_observation(tokens[GRID_SIZE + 1], line)?;
+                for &cell in &tokens[1..=GRID_SIZE] {
+                    match cell {
+                        "o" => atoms_in_cells += 1,
+                        "." | "?" => {}
+                        _ => {
+                            return Err(ParsePuzzleError::UnknownSymbol {
+                                line,
+                                symbol: cell.to_owned(),
+                            })
+                        }
+                    }
+                }
+            }
+        }
+
+        let atom_count = match atom_count {
+            Some(count) => count,
+            None if atoms_in_cells > 0 => atoms_in_cells,
+            None => return Err(ParsePuzzleError::MissingAtomCount),
+        };
+        let observations =
+            Observations::from_sides(sides, ruleset).ok_or(ParsePuzzleError::UnpairedLetter)?;
+
+        Ok(Puzzle {
+            atom_count,
+            observations,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brute_force;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn puzzle_text_round_trip() {
+        for ruleset in [Ruleset::Classic, Ruleset::Plain] {
+            for _ in 0..100 {
+                let grid = AtomGrid::random(5);
+                let puzzle = Puzzle::from_grid(&grid, 5, ruleset);
+                let parsed: Puzzle = puzzle.to_string().parse().expect("valid puzzle");
+                assert_eq!(puzzle, parsed);
+            }
+        }
+    }
+
+    #[test]
+    fn uniqueness_agrees_with_brute_force() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..20 {
+            let grid = AtomGrid::random_with(5, &mut rng);
+            let puzzle = Puzzle::from_grid(&grid, 5, Ruleset::Classic);
+            assert_eq!(puzzle.is_unique(), brute_force::is_unique(&puzzle));
+        }
+    }
+
+    #[test]
+    fn atom_count_from_solution_cells() {
+        let grid = AtomGrid::from_bitboard(54043333103714304);
+        let observations = Observations::observe_all(&grid, Ruleset::Classic);
+        let text = crate::observation::draw(&grid, &observations).unwrap();
+        let parsed: Puzzle = text.parse().expect("valid puzzle");
+        assert_eq!(parsed.atom_count, 5);
+        assert_eq!(parsed.observations, observations);
+    }
+
+    #[test]
+    fn unpaired_letter_is_rejected() {
+        let grid = AtomGrid::random(5);
+        let text = Puzzle::from_grid(&grid, 5, Ruleset::Classic).to_string();
+        // Turn the first letter into an unpaired one.
+        let letter = text
+            .lines()
+            .skip(1)
+            .flat_map(|l| l.split_whitespace())
+            .find(|t| t.len() == 1 && t.chars().all(|c| c.is_ascii_uppercase()));
+        if let Some(letter) = letter {
+            let broken = text.replacen(letter, "Z", 1);
+            assert_eq!(
+                broken.parse::<Puzzle>(),
+                Err(ParsePuzzleError::UnpairedLetter)
+            );
+        }
+    }
+}
@@ -0,0 +1,372 @@
+//! Solves puzzles by encoding the laser rules into clauses for our own small CDCL solver, as an
+//! alternative to the brute force search.
+//!
+//! There is one variable per cell telling if it holds an atom. Every probe gets its own set of
+//! beam variables, one per cell and direction, which are true where the laser passes by. The
+//! movement rules of [LaserTip::move_once] become clauses "beam here and these atoms around, so
+//! beam there". For a probe which comes out again, every beam variable also needs a reason to be
+//! true, so the only way to reach the observed exit is the real path of the laser.
+//!
+//! Like the rest of the crate, the encoding is for the box of [GRID_SIZE] × [GRID_SIZE] cells.
+
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::cdcl::{Lit, Solver, Var};
+use crate::i8vec2::I8Vec2;
+use crate::laser::{Direction, LaserTip, Ruleset};
+use crate::observation::{Observations, LASER_ABSORBED, LASER_REFLECTED, NOT_PROBED};
+use crate::puzzle::Puzzle;
+use std::collections::HashMap;
+
+/// Finds up to `limit` grids which have the right amount of atoms and explain all observations,
+/// like [crate::brute_force::find_solutions].
+pub fn find_solutions(puzzle: &Puzzle, limit: usize) -> Vec<AtomGrid> {
+    let mut model = Model::new(puzzle);
+    let mut solutions = vec![];
+    while solutions.len() < limit {
+        let Some(values) = model.solver.solve() else {
+            break;
+        };
+        let mut grid = AtomGrid::default();
+        for (i, atom) in model.atoms.iter().enumerate() {
+            if values[atom.index()] {
+                grid.set(cell(i), true);
+            }
+        }
+        // The next solution needs to leave out at least one of these atoms.
+        let block: Vec<Lit> = grid
+            .atoms()
+            .map(|v| Lit::negative(model.atoms[index(v)]))
+            .collect();
+        model.solver.add_clause(&block);
+        solutions.push(grid);
+    }
+    solutions
+}
+
+fn index(v: I8Vec2) -> usize {
+    v.y as usize * GRID_SIZE + v.x as usize
+}
+
+fn cell(i: usize) -> I8Vec2 {
+    I8Vec2::new((i % GRID_SIZE) as i8, (i / GRID_SIZE) as i8)
+}
+
+struct Model {
+    solver: Solver,
+    atoms: Vec<Var>,
+}
+
+/// One step of the laser, possible if all `conditions` hold. `from` is None for the laser entering
+/// the box, `to` is None if the step leads somewhere the laser can not be.
+struct Step {
+    from: Option<Var>,
+    conditions: Vec<Lit>,
+    to: Option<Var>,
+}
+
+impl Model {
+    fn new(puzzle: &Puzzle) -> Self {
+        let mut solver = Solver::new();
+        let atoms: Vec<Var> = (0..GRID_SIZE * GRID_SIZE)
+            .map(|_| solver.new_var())
+            .collect();
+        let mut model = Model { solver, atoms };
+        model.exactly(puzzle.atom_count as usize);
+
+        let observations = &puzzle.observations;
+        for (direction, shift, obs) in observations.iter() {
+            if obs == NOT_PROBED {
+                continue;
+            }
+            let entry = LaserTip::new(shift, direction).position();
+            let target = if obs == LASER_ABSORBED {
+                None
+            } else if obs == LASER_REFLECTED {
+                Some(entry)
+            } else {
+                let (out_direction, out_shift) = partner(observations, direction, shift);
+                if (out_direction as usize, out_shift) < (direction as usize, shift) {
+                    continue; // The partner already covers the same path backwards.
+                }
+                Some(LaserTip::new(out_shift, out_direction).position())
+            };
+            model.add_probe(shift, direction, target, observations.ruleset);
+        }
+        model
+    }
+
+    /// The literal that there is (or is not) an atom. Outside the grid there are no atoms, so the
+    /// literal is either always true (Ok(None)) or never (Err).
+    fn atom(&self, v: I8Vec2, atom: bool) -> Result<Option<Lit>, ()> {
+        match (v.in_grid(), atom) {
+            (true, _) => Ok(Some(Lit::new(self.atoms[index(v)], atom))),
+            (false, false) => Ok(None),
+            (false, true) => Err(()),
+        }
+    }
+
+    /// Conditions on the cell in front and the two front corners, or Err if they are impossible.
+    fn surroundings(
+        &self,
+        position: I8Vec2,
+        direction: Direction,
+        left: bool,
+        right: bool,
+    ) -> Result<Vec<Lit>, ()> {
+        let front = position + direction.dxy();
+        Ok([
+            self.atom(front, false)?,
+            self.atom(front + direction.counter_clockwise().dxy(), left)?,
+            self.atom(front + direction.clockwise().dxy(), right)?,
+        ]
+        .into_iter()
+        .flatten()
+        .collect())
+    }
+
+    /// Adds the beam of a laser shone in from the border. It must leave the box at `target`, or
+    /// never come out again if there is no target.
+    fn add_probe(
+        &mut self,
+        shift: u8,
+        direction: Direction,
+        target: Option<I8Vec2>,
+        ruleset: Ruleset,
+    ) {
+        let beams: Vec<[Var; 4]> = (0..GRID_SIZE * GRID_SIZE)
+            .map(|_| [(); 4].map(|_| self.solver.new_var()))
+            .collect();
+        let exit = target.map(|position| (position, self.solver.new_var()));
+        // The laser at a position, or None if it must never be there.
+        let beam = |position: I8Vec2, direction: Direction| -> Option<Var> {
+            if position.in_grid() {
+                Some(beams[index(position)][direction as usize])
+            } else {
+                exit.filter(|&(p, _)| p == position).map(|(_, var)| var)
+            }
+        };
+
+        let mut steps = vec![];
+        let mut step = |from, conditions: Result<Vec<Lit>, ()>, to| {
+            if let Ok(conditions) = conditions {
+                steps.push(Step {
+                    from,
+                    conditions,
+                    to,
+                });
+            }
+        };
+
+        // Entering the box follows the edge rules, see LaserTip::traverse_grid.
+        let entry = LaserTip::new(shift, direction).position();
+        let reflection = beam(entry, direction.flip());
+        step(
+            None,
+            self.surroundings(entry, direction, false, false),
+            beam(entry + direction.dxy(), direction),
+        );
+        step(
+            None,
+            self.surroundings(entry, direction, true, true),
+            reflection,
+        );
+        if ruleset == Ruleset::Classic {
+            step(
+                None,
+                self.surroundings(entry, direction, true, false),
+                reflection,
+            );
+            step(
+                None,
+                self.surroundings(entry, direction, false, true),
+                reflection,
+            );
+        }
+
+        // Rules 2 to 5 of LaserTip::move_once for every position inside the box. Being absorbed
+        // by rule 1 ends the path, which needs no clause.
+        for (i, here) in beams.iter().enumerate() {
+            let position = cell(i);
+            for d in Direction::all() {
+                let from = Some(here[d as usize]);
+                let front = position + d.dxy();
+                if !front.in_grid() {
+                    step(from, Ok(vec![]), beam(front, d));
+                    continue;
+                }
+                step(
+                    from,
+                    self.surroundings(position, d, false, false),
+                    beam(front, d),
+                );
+                step(
+                    from,
+                    self.surroundings(position, d, true, true),
+                    beam(position - d.dxy(), d.flip()),
+                );
+                step(
+                    from,
+                    self.surroundings(position, d, true, false),
+                    beam(position + d.clockwise().dxy(), d.clockwise()),
+                );
+                step(
+                    from,
+                    self.surroundings(position, d, false, true),
+                    beam(
+                        position + d.counter_clockwise().dxy(),
+                        d.counter_clockwise(),
+                    ),
+                );
+            }
+        }
+
+        // The laser follows every step that is possible.
+        for step in &steps {
+            let mut clause: Vec<Lit> = step.conditions.iter().map(|&l| !l).collect();
+            clause.extend(step.from.map(Lit::negative));
+            clause.extend(step.to.map(Lit::positive));
+            self.solver.add_clause(&clause);
+        }
+
+        let Some((_, exit)) = exit else {
+            return;
+        };
+        // The laser must come out at the target, and it must get there for a reason.
+        self.solver.add_clause(&[Lit::positive(exit)]);
+        let mut reasons: HashMap<Var, Vec<Lit>> = HashMap::new();
+        for step in &steps {
+            let Some(to) = step.to else {
+                continue;
+            };
+            let taken = self.solver.new_var();
+            for &condition in &step.conditions {
+                self.solver.add_clause(&[Lit::negative(taken), condition]);
+            }
+            if let Some(from) = step.from {
+                self.solver
+                    .add_clause(&[Lit::negative(taken), Lit::positive(from)]);
+            }
+            reasons.entry(to).or_default().push(Lit::positive(taken));
+        }
+        for var in beams.iter().flatten().copied().chain([exit]) {
+            let mut clause = reasons.remove(&var).unwrap_or_default();
+            clause.push(Lit::negative(var));
+            self.solver.add_clause(&clause);
+        }
+    }
+
+    /// Exactly `count` atoms, with a sequential counter: `counter[i][j]` is true if there are more
+    /// than `j` atoms among the first `i + 1` cells.
+    fn exactly(&mut self, count: usize) {
+        let n = self.atoms.len();
+        if count == 0 {
+            for i in 0..n {
+                self.solver.add_clause(&[Lit::negative(self.atoms[i])]);
+            }
+            return;
+        }
+        if count > n {
+            self.solver.add_clause(&[]);
+            return;
+        }
+        let counter: Vec<Vec<Var>> = (0..n)
+            .map(|_| (0..count).map(|_| self.solver.new_var()).collect())
+            .collect();
+        for i in 0..n {
+            let atom = self.atoms[i];
+            for j in 0..count {
+                let c = Lit::positive(counter[i][j]);
+                // Counting up.
+                if j == 0 {
+                    self.solver.add_clause(&[Lit::negative(atom), c]);
+                }
+                if i > 0 {
+                    let before = Lit::positive(counter[i - 1][j]);
+                    self.solver.add_clause(&[!before, c]);
+                    if j > 0 {
+                        let less = Lit::positive(counter[i - 1][j - 1]);
+                        self.solver.add_clause(&[Lit::negative(atom), !less, c]);
+                    }
+                }
+                // And only counting what is there.
+                if i == 0 {
+                    if j == 0 {
+                        self.solver.add_clause(&[!c, Lit::positive(atom)]);
+                    } else {
+                        self.solver.add_clause(&[!c]);
+                    }
+                } else {
+                    let before = Lit::positive(counter[i - 1][j]);
+                    self.solver.add_clause(&[!c, before, Lit::positive(atom)]);
+                    if j > 0 {
+                        let less = Lit::positive(counter[i - 1][j - 1]);
+                        self.solver.add_clause(&[!c, before, less]);
+                    }
+                }
+            }
+            // Not more than `count`.
+            if i > 0 {
+                let full = Lit::positive(counter[i - 1][count - 1]);
+                self.solver.add_clause(&[Lit::negative(atom), !full]);
+            }
+        }
+        // And not less.
+        self.solver
+            .add_clause(&[Lit::positive(counter[n - 1][count - 1])]);
+    }
+}
+
+/// Where the laser shone in at the given position comes out, according to its letter.
+fn partner(observations: &Observations, direction: Direction, shift: u8) -> (Direction, u8) {
+    let obs = observations.sides[direction as usize][shift as usize];
+    observations
+        .iter()
+        .into_iter()
+        .find(|&(d, s, o)| o == obs && (d, s) != (direction, shift))
+        .map(|(d, s, _)| (d, s))
+        .expect("Letters come in pairs.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brute_force;
+
+    #[test]
+    fn same_solutions_as_brute_force() {
+        for ruleset in [Ruleset::Classic, Ruleset::Plain] {
+            for atom_count in [1, 3, 5] {
+                for _ in 0..3 {
+                    let grid = AtomGrid::random(atom_count);
+                    let puzzle = Puzzle::from_grid(&grid, atom_count, ruleset);
+                    let mut expected = brute_force::find_solutions(&puzzle, 20);
+                    let mut solutions = find_solutions(&puzzle, 20);
+                    expected.sort();
+                    solutions.sort();
+                    assert_eq!(solutions, expected, "{}", grid.as_bitboard());
+                }
+            }
+        }
+    }
+
+    /// With only a few probes, there are many solutions to find.
+    #[test]
+    fn partially_probed_puzzles() {
+        let grid = AtomGrid::random(3);
+        let mut observations = Observations::new(Ruleset::Classic);
+        for shift in [1, 4, 6] {
+            observations.probe(LaserTip::new(shift, Direction::Right), &grid);
+            observations.probe(LaserTip::new(shift, Direction::Down), &grid);
+        }
+        let puzzle = Puzzle {
+            atom_count: 3,
+            observations,
+        };
+        let mut expected = brute_force::find_solutions(&puzzle, usize::MAX);
+        let mut solutions = find_solutions(&puzzle, usize::MAX);
+        expected.sort();
+        solutions.sort();
+        assert_eq!(solutions, expected, "{}", grid.as_bitboard());
+        assert!(solutions.contains(&grid));
+    }
+}
@@ -0,0 +1,489 @@
+//! A small HTTP server for playing against a hidden grid, built with `--features server` and
+//! started with the `laser-puzzle-server` binary. It keeps its games in memory and answers in
+//! JSON, with cells and sides in the same shapes as the command line.
+//!
+//! | Request                  | Body                                   | Answer                      |
+//! |--------------------------|----------------------------------------|-----------------------------|
+//! | `POST /games`            | `{"atoms": 5, "rules": "classic", "difficulty": "easy", "grading": "exact", "seed": 1}`, all optional | the new game |
+//! | `GET /games/{id}`        |                                        | the game                    |
+//! | `POST /games/{id}/probe` | `{"side": "left", "shift": 3}`         | what happened, and the game |
+//! | `POST /games/{id}/guess` | `{"atoms": [[0, 1], [5, 3], ...]}`     | the score, and the game     |
+//! | `DELETE /games/{id}`     |                                        | nothing                     |
+//!
+//! Scores are those of the original Black Box: one point for every marker on the sides, so two
+//! for a laser which comes out somewhere else, and five for every atom in the wrong place. Lower
+//! is better. Probing a position which already shows a marker answers the same again and costs
+//! nothing. A game is over after the first guess and shows its solution then.
+//!
+//! With `"grading": "observations"` a guess is also correct if it has as many atoms and looks the
+//! same from all sides, see [Grading].
+
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::generator::{self, Difficulty};
+use crate::guess::{self, Grading};
+use crate::json::{atoms_json, parse_atoms, parse_side, side_name, sides_json};
+use crate::laser::{LaserTip, Ruleset};
+use crate::observation::{self, Observations, ProbeResult, NOT_PROBED};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Points for an atom in the wrong place.
+const WRONG_ATOM_PENALTY: usize = 5;
+
+/// The most atoms a game can have. From 11 on a new puzzle takes seconds to generate, and the
+/// server answers one request at a time.
+const MAX_ATOMS: u64 = 10;
+
+/// An HTTP status code and the JSON to send along.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    /// The HTTP status code.
+    pub status: u16,
+    /// The body, `null` for no content.
+    pub body: Value,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Response { status: 200, body }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Response {
+            status,
+            body: json!({ "error": message.into() }),
+        }
+    }
+}
+
+struct Game {
+    grid: AtomGrid,
+    observations: Observations,
+    grading: Grading,
+    /// Set once the player made their guess.
+    score: Option<usize>,
+}
+
+impl Game {
+    /// One point per marker on the sides.
+    fn markers(&self) -> usize {
+        let observations = self.observations.iter().into_iter();
+        observations
+            .filter(|&(_, _, obs)| obs != NOT_PROBED)
+            .count()
+    }
+
+    fn json(&self, id: &str) -> Value {
+        let mut json = json!({
+            "id": id,
+            "atoms": self.grid.atom_count(),
+            "rules": self.observations.ruleset.to_string(),
+            "grading": self.grading.to_string(),
+            "sides": sides_json(&self.observations),
+            "markers": self.markers(),
+            "finished": self.score.is_some(),
+        });
+        if let Some(score) = self.score {
+            json["score"] = json!(score);
+            json["solution"] = atoms_json(&self.grid);
+        }
+        json
+    }
+}
+
+/// All running games. The server hands every request to [Games::handle].
+pub struct Games {
+    games: HashMap<String, Game>,
+    rng: StdRng,
+}
+
+impl Games {
+    /// No games yet. The seed decides the game ids and the puzzles of games without a seed.
+    pub fn new(seed: u64) -> Self {
+        Games {
+            games: HashMap::new(),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Answers a request, see the [module documentation](self) for what there is.
+    pub fn handle(&mut self, method: &str, path: &str, body: &str) -> Response {
+        let path = path.split('?').next().unwrap_or_default();
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let body: Value = if body.trim().is_empty() {
+            json!({})
+        } else {
+            match serde_json::from_str(body) {
+                Ok(body) => body,
+                Err(e) => return Response::error(400, format!("invalid JSON: {}", e)),
+            }
+        };
+        let result = match (method, segments.as_slice()) {
+            ("POST", ["games"]) => self.create(&body),
+            ("GET", ["games", id]) => self.game(id).map(|game| Response::ok(game.json(id))),
+            ("DELETE", ["games", id]) => match self.games.remove(*id) {
+                Some(_) => Ok(Response {
+                    status: 204,
+                    body: Value::Null,
+                }),
+                None => Err(not_found(id)),
+            },
+            ("POST", ["games", id, "probe"]) => self.probe(id, &body),
+            ("POST", ["games", id, "guess"]) => self.guess(id, &body),
+            (_, ["games"] | ["games", _] | ["games", _, "probe" | "guess"]) => Err(
+                Response::error(405, format!("{} is not allowed here", method)),
+            ),
+            _ => Err(Response::error(404, format!("nothing at {}", path))),
+        };
+        result.unwrap_or_else(|error| error)
+    }
+
+    fn create(&mut self, body: &Value) -> Result<Response, Response> {
+        let bad_request = |e: String| Response::error(400, e);
+        let atoms = match body.get("atoms") {
+            None => 5,
+            Some(atoms) => atoms
+                .as_u64()
+                .filter(|&atoms| atoms <= MAX_ATOMS)
+                .ok_or_else(|| bad_request(format!("atoms must be a number up to {}", MAX_ATOMS)))?
+                as u8,
+        };
+        let rules: Ruleset = match body.get("rules").and_then(Value::as_str) {
+            None => Ruleset::default(),
+            Some(rules) => rules.parse().map_err(bad_request)?,
+        };
+        let difficulty = match body.get("difficulty").and_then(Value::as_str) {
+            None => None,
+            Some(difficulty) => Some(difficulty.parse::<Difficulty>().map_err(bad_request)?),
+        };
+        let grading: Grading = match body.get("grading").and_then(Value::as_str) {
+            None => Grading::default(),
+            Some(grading) => grading.parse().map_err(bad_request)?,
+        };
+        let mut rng = match body.get("seed").and_then(Value::as_u64) {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::seed_from_u64(self.rng.gen()),
+        };
+        let (grid, _) =
+            generator::generate(atoms, rules, difficulty, &mut rng).ok_or_else(|| {
+                Response::error(
+                    422,
+                    "no puzzle with a unique solution found, try fewer atoms",
+                )
+            })?;
+
+        let id = format!("{:016x}", self.rng.gen::<u64>());
+        let game = Game {
+            grid,
+            observations: Observations::new(rules),
+            grading,
+            score: None,
+        };
+        let body = game.json(&id);
+        self.games.insert(id, game);
+        Ok(Response { status: 201, body })
+    }
+
+    fn probe(&mut self, id: &str, body: &Value) -> Result<Response, Response> {
+        let side = body.get("side").and_then(Value::as_str).unwrap_or_default();
+        let direction = parse_side(side).map_err(|e| Response::error(400, e))?;
+        let shift = body
+            .get("shift")
+            .and_then(Value::as_u64)
+            .filter(|&shift| (shift as usize) < GRID_SIZE)
+            .ok_or_else(|| {
+                Response::error(400, format!("shift must be a number below {}", GRID_SIZE))
+            })?;
+        let game = self.open_game(id)?;
+
+        let laser = LaserTip::new(shift as u8, direction);
+        game.observations.probe(laser, &game.grid);
+        let mut json = match observation::shoot(laser, &game.grid, game.observations.ruleset) {
+            ProbeResult::Absorbed => json!({ "result": "absorbed" }),
+            ProbeResult::Reflected => json!({ "result": "reflected" }),
+            ProbeResult::Exit(out_shift, out_direction) => json!({
+                "result": "exit",
+                "exit": { "side": side_name(out_direction), "shift": out_shift },
+            }),
+        };
+        json["game"] = game.json(id);
+        Ok(Response::ok(json))
+    }
+
+    fn guess(&mut self, id: &str, body: &Value) -> Result<Response, Response> {
+        let guess = parse_atoms(body.get("atoms").unwrap_or(&Value::Null))
+            .map_err(|e| Response::error(400, e))?;
+        let game = self.open_game(id)?;
+        if guess.atom_count() != game.grid.atom_count() {
+            return Err(Response::error(
+                400,
+                format!(
+                    "the box holds {} atoms, the guess has {}",
+                    game.grid.atom_count(),
+                    guess.atom_count()
+                ),
+            ));
+        }
+
+        let ruleset = game.observations.ruleset;
+        let correct = guess::grade(&guess, &game.grid, ruleset, game.grading);
+        let wrong = if correct {
+            AtomGrid::default()
+        } else {
+            guess.difference(&game.grid)
+        };
+        let score = game.markers() + WRONG_ATOM_PENALTY * wrong.atom_count() as usize;
+        game.score = Some(score);
+        Ok(Response::ok(json!({
+            "correct": correct,
+            "score": score,
+            "wrong": atoms_json(&wrong),
+            "game": game.json(id),
+        })))
+    }
+
+    fn game(&self, id: &str) -> Result<&Game, Response> {
+        self.games.get(id).ok_or_else(|| not_found(id))
+    }
+
+    /// A game which is not over yet.
+    fn open_game(&mut self, id: &str) -> Result<&mut Game, Response> {
+        let game = self.games.get_mut(id).ok_or_else(|| not_found(id))?;
+        match game.score {
+            None => Ok(game),
+            Some(_) => Err(Response::error(409, format!("game {} is over", id))),
+        }
+    }
+}
+
+fn not_found(id: &str) -> Response {
+    Response::error(404, format!("no game {}", id))
+}
+
+/// Answers requests one after the other until [tiny_http::Server::unblock] is called. A request
+/// which panics gets a 500 and doesn't stop the server.
+pub fn serve(server: &tiny_http::Server, games: &mut Games) {
+    for mut request in server.incoming_requests() {
+        let mut body = String::new();
+        let response = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => panic::catch_unwind(AssertUnwindSafe(|| {
+                games.handle(request.method().as_str(), request.url(), &body)
+            }))
+            .unwrap_or_else(|_| Response::error(500, "internal error")),
+            Err(e) => Response::error(400, format!("unreadable body: {}", e)),
+        };
+        let data = match response.body {
+            Value::Null => String::new(),
+            body => body.to_string(),
+        };
+        let content_type = tiny_http::Header::from_bytes("Content-Type", "application/json")
+            .expect("The header is valid.");
+        let answer = tiny_http::Response::from_string(data)
+            .with_status_code(response.status)
+            .with_header(content_type);
+        // The client may have gone away already, that is its problem.
+        let _ = request.respond(answer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brute_force;
+    use crate::puzzle::Puzzle;
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+
+    fn new_game(games: &mut Games, body: &str) -> String {
+        let response = games.handle("POST", "/games", body);
+        assert_eq!(response.status, 201, "{}", response.body);
+        response.body["id"].as_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn play_a_game() {
+        let mut games = Games::new(1);
+        let id = new_game(&mut games, r#"{"atoms": 4, "seed": 7}"#);
+        let grid = games.games[&id].grid;
+
+        let probe = games.handle(
+            "POST",
+            &format!("/games/{}/probe", id),
+            r#"{"side": "left", "shift": 2}"#,
+        );
+        assert_eq!(probe.status, 200);
+        let markers = match probe.body["result"].as_str().unwrap() {
+            "exit" => 2,
+            _ => 1,
+        };
+        assert_eq!(probe.body["game"]["markers"], markers);
+
+        // Probing the same position again gives the same answer and costs nothing, even more
+        // often than there are letters.
+        for _ in 0..30 {
+            let again = games.handle(
+                "POST",
+                &format!("/games/{}/probe", id),
+                r#"{"side": "left", "shift": 2}"#,
+            );
+            assert_eq!(again, probe);
+        }
+
+        // Move one atom to a wrong cell.
+        let mut guess = grid;
+        let first = grid.atoms().next().unwrap();
+        let empty = AtomGrid::from_bits(!grid.bits()).atoms().next().unwrap();
+        guess.set(first, false);
+        guess.set(empty, true);
+        let body = json!({ "atoms": atoms_json(&guess) }).to_string();
+        let guessed = games.handle("POST", &format!("/games/{}/guess", id), &body);
+        assert_eq!(guessed.status, 200);
+        assert_eq!(guessed.body["correct"], false);
+        assert_eq!(guessed.body["score"], markers + WRONG_ATOM_PENALTY);
+        assert_eq!(guessed.body["wrong"], json!([[empty.x, empty.y]]));
+        assert_eq!(guessed.body["game"]["solution"], atoms_json(&grid));
+
+        let again = games.handle("POST", &format!("/games/{}/guess", id), &body);
+        assert_eq!(again.status, 409);
+        let deleted = games.handle("DELETE", &format!("/games/{}", id), "");
+        assert_eq!(deleted.status, 204);
+        assert_eq!(
+            games.handle("GET", &format!("/games/{}", id), "").status,
+            404
+        );
+    }
+
+    #[test]
+    fn alternative_solutions_can_be_accepted() {
+        let mut games = Games::new(1);
+        let id = new_game(&mut games, r#"{"atoms": 4, "grading": "observations"}"#);
+        assert_eq!(games.games[&id].grading, Grading::Observations);
+
+        // Generated puzzles only have one solution, so put in a game whose atoms look just like
+        // other ones.
+        let mut rng = StdRng::seed_from_u64(1);
+        let (grid, other) = loop {
+            let grid = AtomGrid::random_with(5, &mut rng);
+            let puzzle = Puzzle::from_grid(&grid, 5, Ruleset::Classic);
+            if let Some(&other) = brute_force::find_solutions(&puzzle, 2)
+                .iter()
+                .find(|&&other| other != grid)
+            {
+                break (grid, other);
+            }
+        };
+        for (grading, correct) in [(Grading::Exact, false), (Grading::Observations, true)] {
+            let game = Game {
+                grid,
+                observations: Observations::new(Ruleset::Classic),
+                grading,
+                score: None,
+            };
+            games.games.insert(id.clone(), game);
+            let body = json!({ "atoms": atoms_json(&other) }).to_string();
+            let guessed = games.handle("POST", &format!("/games/{}/guess", id), &body);
+            assert_eq!(guessed.status, 200);
+            assert_eq!(guessed.body["correct"], correct);
+            assert_eq!(guessed.body["score"] == 0, correct);
+        }
+    }
+
+    #[test]
+    fn games_with_the_most_atoms() {
+        let mut games = Games::new(1);
+        let id = new_game(
+            &mut games,
+            &json!({ "atoms": MAX_ATOMS, "seed": 1 }).to_string(),
+        );
+        assert_eq!(games.games[&id].grid.atom_count() as u64, MAX_ATOMS);
+        let more = json!({ "atoms": MAX_ATOMS + 1 }).to_string();
+        assert_eq!(games.handle("POST", "/games", &more).status, 400);
+    }
+
+    #[test]
+    fn bad_requests() {
+        let mut games = Games::new(1);
+        let id = new_game(&mut games, r#"{"atoms": 3, "seed": 1}"#);
+        let probe = format!("/games/{}/probe", id);
+        let status =
+            |games: &mut Games, method, path: &str, body| games.handle(method, path, body).status;
+        assert_eq!(
+            status(&mut games, "POST", "/games", r#"{"atoms": 99}"#),
+            400
+        );
+        assert_eq!(
+            status(&mut games, "POST", "/games", r#"{"rules": "x"}"#),
+            400
+        );
+        assert_eq!(status(&mut games, "POST", "/games", "{"), 400);
+        assert_eq!(
+            status(&mut games, "POST", "/games", r#"{"grading": "kind"}"#),
+            400
+        );
+        assert_eq!(status(&mut games, "POST", &probe, r#"{"side": "up"}"#), 400);
+        let shift = r#"{"side": "top", "shift": 8}"#;
+        assert_eq!(status(&mut games, "POST", &probe, shift), 400);
+        let few = r#"{"atoms": [[0, 0]]}"#;
+        assert_eq!(
+            status(&mut games, "POST", &format!("/games/{}/guess", id), few),
+            400
+        );
+        assert_eq!(status(&mut games, "GET", "/games/nope", ""), 404);
+        assert_eq!(status(&mut games, "GET", "/elsewhere", ""), 404);
+        assert_eq!(status(&mut games, "PUT", &probe, ""), 405);
+    }
+
+    /// Sends a request the way any HTTP client would and returns the status and body.
+    fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut answer = String::new();
+        stream.read_to_string(&mut answer).unwrap();
+        let (head, body) = answer.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        let body = if body.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_str(body).unwrap()
+        };
+        (status, body)
+    }
+
+    #[test]
+    fn serve_over_http() {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+        std::thread::scope(|scope| {
+            scope.spawn(|| serve(&server, &mut Games::new(3)));
+
+            let (status, game) = request(addr, "POST", "/games", r#"{"atoms": 3}"#);
+            assert_eq!(status, 201);
+            let id = game["id"].as_str().unwrap();
+            assert_eq!(game["sides"]["top"][0], "?");
+
+            let path = format!("/games/{}/probe", id);
+            let (status, probe) = request(addr, "POST", &path, r#"{"side":"top","shift":0}"#);
+            assert_eq!(status, 200);
+            assert_ne!(probe["game"]["sides"]["top"][0], "?");
+
+            let (status, _) = request(addr, "DELETE", &format!("/games/{}", id), "");
+            assert_eq!(status, 204);
+            let (status, error) = request(addr, "GET", &format!("/games/{}", id), "");
+            assert_eq!(status, 404);
+            assert!(error["error"].as_str().unwrap().contains(id));
+
+            server.unblock();
+        });
+    }
+}
@@ -0,0 +1,331 @@
+//! A game in progress: the hidden atoms, what the player probed so far, their [Board] and a log
+//! of every move. Replaying the log on the same atoms gives the same session again, which is also how
+//! a session is saved, see [json::session_json](crate::json::session_json).
+
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::board::{Board, Conflict, Mark};
+use crate::guess::{self, Grading, GuessCheck};
+use crate::i8vec2::I8Vec2;
+use crate::laser::{Direction, LaserTip, Ruleset};
+use crate::observation::{self, Observations, ProbeResult};
+use crate::puzzle::Puzzle;
+use std::cell::OnceCell;
+
+/// Something the player did, in the order of the log.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Move {
+    /// Shone a laser in, given as the parameters of [LaserTip::new].
+    Probe(u8, Direction),
+    /// Marked a cell, or cleared the mark with `None`.
+    Mark(I8Vec2, Option<Mark>),
+    /// Wrote a note at a position on the border, or cleared it with `None`.
+    Note(u8, Direction, Option<String>),
+    /// Took back the last mark.
+    Undo,
+    /// Made the last mark which was taken back again.
+    Redo,
+    /// Guessed that these are the atoms.
+    Guess(AtomGrid),
+}
+
+/// A mark as it can be undone and redone.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct MarkChange {
+    cell: I8Vec2,
+    before: Option<Mark>,
+    after: Option<Mark>,
+}
+
+/// One player's game against a hidden grid.
+#[derive(Clone, Debug)]
+pub struct GameSession {
+    grid: AtomGrid,
+    observations: Observations,
+    board: Board,
+    log: Vec<Move>,
+    undo: Vec<MarkChange>,
+    redo: Vec<MarkChange>,
+    /// Only worked out when asked for, see [GameSession::unique].
+    unique: OnceCell<bool>,
+}
+
+impl PartialEq for GameSession {
+    fn eq(&self, other: &Self) -> bool {
+        // Whether uniqueness was worked out yet doesn't matter, it is the same for the same grid.
+        self.grid == other.grid
+            && self.observations == other.observations
+            && self.board == other.board
+            && self.log == other.log
+            && self.undo == other.undo
+            && self.redo == other.redo
+    }
+}
+
+impl Eq for GameSession {}
+
+impl GameSession {
+    /// A new game with nothing probed or marked yet.
+    pub fn new(grid: AtomGrid, ruleset: Ruleset) -> Self {
+        GameSession {
+            unique: OnceCell::new(),
+            grid,
+            observations: Observations::new(ruleset),
+            board: Board::default(),
+            log: vec![],
+            undo: vec![],
+            redo: vec![],
+        }
+    }
+
+    /// Plays the moves of a log on a new game. Fails on the first move which is not possible,
+    /// like a probe off the box or an undo with nothing to undo.
+    pub fn replay(grid: AtomGrid, ruleset: Ruleset, moves: &[Move]) -> Result<Self, String> {
+        let mut session = GameSession::new(grid, ruleset);
+        for (i, m) in moves.iter().enumerate() {
+            if !session.play(m.clone()) {
+                return Err(format!("move {} ({:?}) is not possible", i + 1, m));
+            }
+        }
+        Ok(session)
+    }
+
+    /// The hidden atoms. Only show them once the game is over.
+    pub fn grid(&self) -> &AtomGrid {
+        &self.grid
+    }
+
+    /// Whether the hidden atoms are the only ones giving their observations from all sides. If
+    /// not, grading with [Grading::Observations] is fairer. Worked out on the first call, see
+    /// [Puzzle::is_unique].
+    pub fn unique(&self) -> bool {
+        *self.unique.get_or_init(|| {
+            let atom_count = self.grid.atom_count() as u8;
+            Puzzle::from_grid(&self.grid, atom_count, self.observations.ruleset).is_unique()
+        })
+    }
+
+    /// What the probes showed so far.
+    pub fn observations(&self) -> &Observations {
+        &self.observations
+    }
+
+    /// Every move so far, the first one first. Undo and redo are moves as well.
+    pub fn log(&self) -> &[Move] {
+        &self.log
+    }
+
+    /// Shines a laser into the box and writes the result on the sides. Fails for a shift off the
+    /// box.
+    pub fn probe(&mut self, shift: u8, direction: Direction) -> Result<ProbeResult, String> {
+        on_the_border(shift)?;
+        let laser = LaserTip::new(shift, direction);
+        self.observations.probe(laser, &self.grid);
+        self.log.push(Move::Probe(shift, direction));
+        Ok(observation::shoot(
+            laser,
+            &self.grid,
+            self.observations.ruleset,
+        ))
+    }
+
+    /// The player's marks and notes.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Where the player's marks contradict the observations, see [Board::conflicts].
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        self.board.conflicts(&self.observations)
+    }
+
+    /// Marks a cell, or clears it with `None`. Marks which were taken back can't be redone after
+    /// this. Fails for a cell outside the box.
+    pub fn set_mark(&mut self, cell: I8Vec2, mark: Option<Mark>) -> Result<(), String> {
+        if !cell.in_grid() {
+            return Err(format!("cell {},{} is not in the box", cell.x, cell.y));
+        }
+        let before = self.board.mark(cell);
+        self.board.set_mark(cell, mark);
+        self.undo.push(MarkChange {
+            cell,
+            before,
+            after: mark,
+        });
+        self.redo.clear();
+        self.log.push(Move::Mark(cell, mark));
+        Ok(())
+    }
+
+    /// Takes back the last mark. False if there is none.
+    pub fn undo(&mut self) -> bool {
+        let Some(change) = self.undo.pop() else {
+            return false;
+        };
+        self.board.set_mark(change.cell, change.before);
+        self.redo.push(change);
+        self.log.push(Move::Undo);
+        true
+    }
+
+    /// Makes the last mark which was taken back again. False if there is none.
+    pub fn redo(&mut self) -> bool {
+        let Some(change) = self.redo.pop() else {
+            return false;
+        };
+        self.board.set_mark(change.cell, change.after);
+        self.undo.push(change);
+        self.log.push(Move::Redo);
+        true
+    }
+
+    /// Writes a note at a position on the border, or clears it with `None`. Notes can't be
+    /// undone, only written over. Fails for a shift off the box.
+    pub fn set_note(
+        &mut self,
+        shift: u8,
+        direction: Direction,
+        note: Option<String>,
+    ) -> Result<(), String> {
+        on_the_border(shift)?;
+        self.board.set_note(shift, direction, note.clone());
+        self.log.push(Move::Note(shift, direction, note));
+        Ok(())
+    }
+
+    /// Guesses the atoms and tells how they compare to the probes so far, see [guess::check].
+    pub fn guess(&mut self, guess: AtomGrid) -> GuessCheck {
+        self.log.push(Move::Guess(guess));
+        guess::check(&guess, &self.grid, &self.observations)
+    }
+
+    /// Whether the guess is right, see [guess::grade]. Grading doesn't count as a move.
+    pub fn grade(&self, guess: &AtomGrid, grading: Grading) -> bool {
+        guess::grade(guess, &self.grid, self.observations.ruleset, grading)
+    }
+
+    /// Makes the move, false if it is not possible.
+    fn play(&mut self, m: Move) -> bool {
+        match m {
+            Move::Probe(shift, direction) => self.probe(shift, direction).is_ok(),
+            Move::Mark(cell, mark) => self.set_mark(cell, mark).is_ok(),
+            Move::Note(shift, direction, note) => self.set_note(shift, direction, note).is_ok(),
+            Move::Undo => self.undo(),
+            Move::Redo => self.redo(),
+            Move::Guess(guess) => {
+                self.guess(guess);
+                true
+            }
+        }
+    }
+
+    /// Whether one of the guesses was exactly the hidden atoms.
+    pub fn solved(&self) -> bool {
+        self.log.contains(&Move::Guess(self.grid))
+    }
+}
+
+fn on_the_border(shift: u8) -> Result<(), String> {
+    if (shift as usize) < GRID_SIZE {
+        Ok(())
+    } else {
+        Err(format!("shift {} is not on the border", shift))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guess::Verdict;
+    use crate::laser::Direction::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn session() -> GameSession {
+        GameSession::new(AtomGrid::from_bitboard(35184640598018), Ruleset::Classic)
+    }
+
+    #[test]
+    fn undo_and_redo_marks() {
+        let mut session = session();
+        let (a, b) = (I8Vec2::new(1, 2), I8Vec2::new(3, 4));
+        session.set_mark(a, Some(Mark::Atom)).unwrap();
+        session.set_mark(a, Some(Mark::Empty)).unwrap();
+        session.set_mark(b, Some(Mark::Atom)).unwrap();
+
+        assert!(session.undo());
+        assert_eq!(session.board().mark(b), None);
+        assert!(session.undo());
+        assert_eq!(session.board().mark(a), Some(Mark::Atom));
+        assert!(session.redo());
+        assert_eq!(session.board().mark(a), Some(Mark::Empty));
+
+        // A new mark drops what could have been redone.
+        session.set_mark(b, Some(Mark::Empty)).unwrap();
+        assert!(!session.redo());
+        assert!(session.undo() && session.undo() && session.undo());
+        assert!(!session.undo());
+        assert_eq!(session.board().atoms(), AtomGrid::default());
+    }
+
+    #[test]
+    fn replay_gives_the_same_session() {
+        let mut session = session();
+        session.probe(3, Right).unwrap();
+        session
+            .set_mark(I8Vec2::new(0, 0), Some(Mark::Atom))
+            .unwrap();
+        session.undo();
+        session.redo();
+        session.probe(5, Down).unwrap();
+        session.set_note(5, Down, Some("A".to_owned())).unwrap();
+        let guess = session.guess(session.board().atoms());
+        assert_eq!(guess.verdict, Verdict::Wrong);
+        assert_eq!(session.guess(*session.grid()).verdict, Verdict::Correct);
+        assert!(session.solved());
+        assert!(session.unique());
+        assert!(session.grade(session.grid(), Grading::Exact));
+
+        let replayed = GameSession::replay(*session.grid(), Ruleset::Classic, session.log());
+        assert_eq!(replayed, Ok(session));
+    }
+
+    #[test]
+    fn replay_rejects_impossible_moves() {
+        let grid = AtomGrid::default();
+        let moves = [Move::Mark(I8Vec2::new(0, 0), Some(Mark::Atom)), Move::Redo];
+        assert!(GameSession::replay(grid, Ruleset::Classic, &moves).is_err());
+        let moves = [Move::Probe(8, Up)];
+        assert!(GameSession::replay(grid, Ruleset::Classic, &moves).is_err());
+        let moves = [Move::Mark(I8Vec2::new(-1, 0), None)];
+        assert!(GameSession::replay(grid, Ruleset::Classic, &moves).is_err());
+        let moves = [Move::Note(9, Left, None)];
+        assert!(GameSession::replay(grid, Ruleset::Classic, &moves).is_err());
+    }
+
+    #[test]
+    fn uniqueness_is_worked_out_when_asked_for() {
+        let grid = AtomGrid::random_with(12, &mut StdRng::seed_from_u64(3));
+        let mut session = GameSession::new(grid, Ruleset::Classic);
+        session.probe(0, Down).unwrap();
+        let replayed = GameSession::replay(grid, Ruleset::Classic, session.log()).unwrap();
+        assert_eq!(session.unique.get(), None);
+        assert_eq!(replayed.unique.get(), None);
+
+        let puzzle = Puzzle::from_grid(&grid, 12, Ruleset::Classic);
+        assert_eq!(session.unique(), puzzle.is_unique());
+        assert_eq!(session.unique.get(), Some(&puzzle.is_unique()));
+        // The cache doesn't count when comparing sessions.
+        assert_eq!(session, replayed);
+    }
+
+    #[test]
+    fn moves_off_the_box_are_refused() {
+        let mut session = session();
+        assert!(session.probe(8, Up).is_err());
+        assert!(session
+            .set_mark(I8Vec2::new(0, 8), Some(Mark::Atom))
+            .is_err());
+        assert!(session.set_note(200, Left, None).is_err());
+        assert_eq!(session, self::session());
+    }
+}
@@ -1,43 +1,184 @@
 //! A solver that takes observations and derives information about the atom grid.
 
-use crate::atom_grid::GRID_SIZE;
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
 use crate::i8vec2::I8Vec2;
 use crate::laser::Direction::{Down, Left, Right, Up};
-use crate::laser::LaserTip;
+use crate::laser::{LaserTip, Ruleset};
 use crate::observation::{Observations, LASER_ABSORBED, LASER_REFLECTED};
 use crate::solver::GridKnowledge::{Empty, Unknown};
-use std::fmt::Write;
+use crate::symmetry::{Symmetry, Transform};
+use std::fmt::{Display, Formatter, Write};
 use GridKnowledge::Atom;
 
-#[derive(Default)]
+/// What is known about each cell of the grid, by the solver or by a player.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct UncertainGrid {
     atoms: [[GridKnowledge; GRID_SIZE]; GRID_SIZE],
 }
 
 impl UncertainGrid {
-    fn get(&self, v: I8Vec2) -> GridKnowledge {
-        self.atoms[v.x as usize][v.y as usize]
+    /// What is known about a cell. Outside the grid there are no atoms.
+    pub fn get(&self, v: I8Vec2) -> GridKnowledge {
+        if v.in_grid() {
+            self.atoms[v.x as usize][v.y as usize]
+        } else {
+            Empty
+        }
+    }
+
+    /// True if we know for sure that there is no atom at this position.
+    pub fn is_empty(&self, v: I8Vec2) -> bool {
+        self.get(v) == Empty
+    }
+
+    /// Cells known to hold an atom, row by row from the top left.
+    pub fn atoms(&self) -> impl Iterator<Item = I8Vec2> + '_ {
+        self.cells_with(Atom)
+    }
+
+    /// Cells known to be empty, row by row from the top left.
+    pub fn empty_cells(&self) -> impl Iterator<Item = I8Vec2> + '_ {
+        self.cells_with(Empty)
+    }
+
+    /// Cells nothing is known about yet, row by row from the top left.
+    pub fn unknown_cells(&self) -> impl Iterator<Item = I8Vec2> + '_ {
+        self.cells_with(Unknown)
+    }
+
+    fn cells_with(&self, knowledge: GridKnowledge) -> impl Iterator<Item = I8Vec2> + '_ {
+        (0..GRID_SIZE)
+            .flat_map(|y| (0..GRID_SIZE).map(move |x| I8Vec2::new(x as i8, y as i8)))
+            .filter(move |&v| self.get(v) == knowledge)
     }
 
-    /// Sets a value, but does nothing if the given vector is outside the grid.
-    fn set_safe(&mut self, v: I8Vec2, knowledge: GridKnowledge) {
-        if knowledge == Unknown {
-            panic!("Can not update with Unknown at {:?}", v);
+    /// Number of cells with this knowledge.
+    pub fn count(&self, knowledge: GridKnowledge) -> usize {
+        self.cells_with(knowledge).count()
+    }
+
+    /// The atom grid, once every cell is known.
+    pub fn to_atom_grid(&self) -> Option<AtomGrid> {
+        if self.unknown_cells().next().is_some() {
+            return None;
         }
-        if v.in_grid() {
-            // Check consistency and crash when updating with inconsistent information.
-            let previous_knowledge = self.atoms[v.x as usize][v.y as usize];
-            if previous_knowledge != Unknown && previous_knowledge != knowledge {
-                panic!(
-                    "Updating existing knowledge {:?} with inconsistent {:?} at {:?}",
-                    previous_knowledge, knowledge, v
-                );
+        let mut grid = AtomGrid::default();
+        for v in self.atoms() {
+            grid.set(v, true);
+        }
+        Some(grid)
+    }
+
+    /// Checks the knowledge against the real atoms. Cells are listed row by row from the top
+    /// left.
+    pub fn compare(&self, truth: &AtomGrid) -> Comparison {
+        let mut comparison = Comparison {
+            correct: vec![],
+            wrong: vec![],
+            unknown: vec![],
+        };
+        for y in 0..GRID_SIZE {
+            for x in 0..GRID_SIZE {
+                let v = I8Vec2::new(x as i8, y as i8);
+                match (self.get(v), truth.get(v)) {
+                    (Unknown, _) => comparison.unknown.push(v),
+                    (Atom, true) | (Empty, false) => comparison.correct.push(v),
+                    (Atom, false) | (Empty, true) => comparison.wrong.push(v),
+                }
             }
+        }
+        comparison
+    }
+
+    /// Starts with what is known already, e.g. from the notes of a player. A cell can't be in
+    /// both grids.
+    pub fn from_known(atoms: &AtomGrid, empty: &AtomGrid) -> Result<Self, Inconsistency> {
+        let mut grid = UncertainGrid::default();
+        for v in atoms.atoms() {
+            grid.set_atom(v)?;
+        }
+        for v in empty.atoms() {
+            grid.set_empty(v)?;
+        }
+        Ok(grid)
+    }
+
+    /// Records that there is an atom. Outside the grid there are no atoms, so that is inconsistent.
+    pub fn set_atom(&mut self, v: I8Vec2) -> Result<(), Inconsistency> {
+        self.set(v, Atom)
+    }
+
+    /// Records that there is no atom. Does nothing if the given vector is outside the grid.
+    pub fn set_empty(&mut self, v: I8Vec2) -> Result<(), Inconsistency> {
+        self.set(v, Empty)
+    }
+
+    fn set(&mut self, v: I8Vec2, knowledge: GridKnowledge) -> Result<(), Inconsistency> {
+        let previous = if v.in_grid() { self.get(v) } else { Empty };
+        if previous != Unknown && previous != knowledge {
+            return Err(Inconsistency {
+                position: v,
+                previous,
+                knowledge,
+            });
+        }
+        if v.in_grid() {
             self.atoms[v.x as usize][v.y as usize] = knowledge;
         }
+        Ok(())
     }
 }
 
+/// How knowledge about a grid matches the real atoms, see [UncertainGrid::compare].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Comparison {
+    /// Cells known to be an atom or empty, and that is right.
+    pub correct: Vec<I8Vec2>,
+    /// Cells known to be an atom or empty, but it is the other way round.
+    pub wrong: Vec<I8Vec2>,
+    /// Cells nothing is known about.
+    pub unknown: Vec<I8Vec2>,
+}
+
+/// Two facts about a cell which contradict each other.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Inconsistency {
+    /// The cell.
+    pub position: I8Vec2,
+    /// What was known before.
+    pub previous: GridKnowledge,
+    /// What was found out or set later on.
+    pub knowledge: GridKnowledge,
+}
+
+impl Display for Inconsistency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cell {},{} is known to be {:?}, but should be {:?}",
+            self.position.x, self.position.y, self.previous, self.knowledge
+        )
+    }
+}
+
+impl std::error::Error for Inconsistency {}
+
+impl Transform for UncertainGrid {
+    fn transformed(&self, symmetry: Symmetry) -> Self {
+        let mut grid = UncertainGrid::default();
+        for x in 0..GRID_SIZE {
+            for y in 0..GRID_SIZE {
+                let v = I8Vec2::new(x as i8, y as i8);
+                let w = symmetry.apply(v);
+                grid.atoms[w.x as usize][w.y as usize] = self.get(v);
+            }
+        }
+        grid
+    }
+}
+
+/// The knowledge with the observations around it, in the puzzle text format: `o` for atoms,
+/// `.` for empty cells and `?` for unknown ones.
 pub fn draw(grid: &UncertainGrid, observations: &Observations) -> Result<String, std::fmt::Error> {
     let mut f = String::new();
     // first, display the row above with lasers pointing down
@@ -75,67 +216,326 @@ pub fn draw(grid: &UncertainGrid, observations: &Observations) -> Result<String,
     Ok(f)
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-enum GridKnowledge {
+/// What is known about a single cell.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
+pub enum GridKnowledge {
+    /// Could be an atom or empty.
+    #[default]
     Unknown,
+    /// Known to hold an atom.
     Atom,
+    /// Known to be empty.
     Empty,
 }
 
-impl Default for GridKnowledge {
-    fn default() -> Self {
-        Unknown
-    }
+/// Applies all rules to the observations and returns the cells they find out about.
+pub fn solve_as_much_as_you_can(observations: &Observations) -> UncertainGrid {
+    // The rules only ever find empty cells, so they can't contradict each other.
+    solve_from(UncertainGrid::default(), observations).expect("The rules are consistent.")
 }
 
-pub fn solve_as_much_as_you_can(observations: &Observations) -> UncertainGrid {
-    let mut grid = UncertainGrid::default();
+/// Like [solve_as_much_as_you_can], but continues from what is known already. Fails if that
+/// contradicts the observations.
+pub fn solve_from(
+    grid: UncertainGrid,
+    observations: &Observations,
+) -> Result<UncertainGrid, Inconsistency> {
+    solve_counting(grid, observations).map(|(grid, _)| grid)
+}
+
+/// Like [solve_from], but also tells how many cells each rule found out about.
+pub fn solve_counting(
+    mut grid: UncertainGrid,
+    observations: &Observations,
+) -> Result<(UncertainGrid, Vec<(Rule, usize)>), Inconsistency> {
+    let mut deductions: Vec<(Rule, usize)> = Rule::all().into_iter().map(|r| (r, 0)).collect();
+    let mut apply = |rule: Rule, grid: &mut UncertainGrid| -> Result<usize, Inconsistency> {
+        let before = grid.count(Unknown);
+        rule.apply(grid, observations)?;
+        let found = before - grid.count(Unknown);
+        deductions[rule as usize].1 += found;
+        Ok(found)
+    };
 
-    letter_finds_four_empty_spaces(&mut grid, observations);
-    reflection_is_not_blocked(&mut grid, observations);
+    apply(Rule::LetterFindsFourEmptySpaces, &mut grid)?;
+    apply(Rule::ReflectionIsNotBlocked, &mut grid)?;
 
-    // Benefits from "Free field" information.
-    absorption_with_one_free_field(&mut grid, observations);
+    // Benefits from "Free field" information, including its own. Repeat until nothing changes, so
+    // the result does not depend on the order of the sides.
+    while apply(Rule::AbsorptionWithOneFreeField, &mut grid)? > 0 {}
 
-    grid
+    Ok((grid, deductions))
 }
 
-fn reflection_is_not_blocked(grid: &mut UncertainGrid, observations: &Observations) {
+/// The rules of the solver, in the order they are applied.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Rule {
+    /// A laser which came out somewhere met no atom on the edge: the cell it entered and the four
+    /// cells around it are empty.
+    LetterFindsFourEmptySpaces,
+    /// A reflected laser was turned back by atoms on both corners, not one in front, so the cell
+    /// it entered is empty.
+    ReflectionIsNotBlocked,
+    /// An absorbed laser which entered an empty cell hit an atom further in, so the cells next to
+    /// the entry are empty too. Only for the classic rules.
+    AbsorptionWithOneFreeField,
+}
+
+impl Rule {
+    /// All rules, in the order they are applied.
+    pub const fn all() -> [Rule; 3] {
+        [
+            Rule::LetterFindsFourEmptySpaces,
+            Rule::ReflectionIsNotBlocked,
+            Rule::AbsorptionWithOneFreeField,
+        ]
+    }
+
+    /// A short name for reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::LetterFindsFourEmptySpaces => "letter",
+            Rule::ReflectionIsNotBlocked => "reflection",
+            Rule::AbsorptionWithOneFreeField => "absorption",
+        }
+    }
+
+    fn apply(
+        self,
+        grid: &mut UncertainGrid,
+        observations: &Observations,
+    ) -> Result<(), Inconsistency> {
+        match self {
+            Rule::LetterFindsFourEmptySpaces => letter_finds_four_empty_spaces(grid, observations),
+            Rule::ReflectionIsNotBlocked => reflection_is_not_blocked(grid, observations),
+            Rule::AbsorptionWithOneFreeField => absorption_with_one_free_field(grid, observations),
+        }
+    }
+}
+
+fn reflection_is_not_blocked(
+    grid: &mut UncertainGrid,
+    observations: &Observations,
+) -> Result<(), Inconsistency> {
     for (direction, shift, obs) in observations.iter() {
         if obs == LASER_REFLECTED {
-            let l = LaserTip::new(shift as u8, direction);
+            let l = LaserTip::new(shift, direction);
             let center = l.forward().position();
 
-            grid.set_safe(center, Empty);
+            grid.set_empty(center)?;
         }
     }
+    Ok(())
 }
 
-fn absorption_with_one_free_field(grid: &mut UncertainGrid, observations: &Observations) {
+fn absorption_with_one_free_field(
+    grid: &mut UncertainGrid,
+    observations: &Observations,
+) -> Result<(), Inconsistency> {
+    // Without the edge rule, a single atom on the corner also makes the laser vanish.
+    if observations.ruleset != Ruleset::Classic {
+        return Ok(());
+    }
     for (direction, shift, obs) in observations.iter() {
         if obs == LASER_ABSORBED {
-            let l = LaserTip::new(shift as u8, direction);
+            let l = LaserTip::new(shift, direction);
             let center = l.forward().position();
 
             if grid.get(center) == Empty {
-                grid.set_safe(center + direction.clockwise().dxy(), Empty);
-                grid.set_safe(center + direction.counter_clockwise().dxy(), Empty);
+                grid.set_empty(center + direction.clockwise().dxy())?;
+                grid.set_empty(center + direction.counter_clockwise().dxy())?;
             }
         }
     }
+    Ok(())
 }
 
-fn letter_finds_four_empty_spaces(grid: &mut UncertainGrid, observations: &Observations) {
+fn letter_finds_four_empty_spaces(
+    grid: &mut UncertainGrid,
+    observations: &Observations,
+) -> Result<(), Inconsistency> {
     for (direction, shift, obs) in observations.iter() {
         if obs.is_letter() {
-            let l = LaserTip::new(shift as u8, direction);
+            let l = LaserTip::new(shift, direction);
             let center = l.forward().position();
 
-            grid.set_safe(center, Empty);
-            grid.set_safe(center + I8Vec2::new(0, 1), Empty);
-            grid.set_safe(center + I8Vec2::new(0, -1), Empty);
-            grid.set_safe(center + I8Vec2::new(1, 0), Empty);
-            grid.set_safe(center + I8Vec2::new(-1, 0), Empty);
+            grid.set_empty(center)?;
+            grid.set_empty(center + I8Vec2::new(0, 1))?;
+            grid.set_empty(center + I8Vec2::new(0, -1))?;
+            grid.set_empty(center + I8Vec2::new(1, 0))?;
+            grid.set_empty(center + I8Vec2::new(-1, 0))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atom_grid::arbitrary_grid;
+    use proptest::prelude::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn ruleset() -> impl Strategy<Value = Ruleset> {
+        prop_oneof![Just(Ruleset::Classic), Just(Ruleset::Plain)]
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(1000))]
+
+        /// Every cell the rules decide on must match the grid the observations came from.
+        #[test]
+        fn deductions_are_sound(grid in arbitrary_grid(0..=12), ruleset in ruleset()) {
+            let observations = Observations::observe_all(&grid, ruleset);
+            let wrong = solve_as_much_as_you_can(&observations).compare(&grid).wrong;
+            prop_assert!(
+                wrong.is_empty(),
+                "Wrong at {:?} for bitboard {} with {} rules",
+                wrong,
+                grid.as_bitboard(),
+                ruleset
+            );
+        }
+
+        /// Knowing some of the real atoms never contradicts the rules.
+        #[test]
+        fn known_atoms_are_consistent(
+            grid in arbitrary_grid(1..=12),
+            ruleset in ruleset(),
+            known in 0usize..12,
+        ) {
+            let observations = Observations::observe_all(&grid, ruleset);
+            let mut atoms = AtomGrid::default();
+            for v in grid.atoms().take(known) {
+                atoms.set(v, true);
+            }
+            let start = UncertainGrid::from_known(&atoms, &AtomGrid::default()).unwrap();
+            let solved = solve_from(start, &observations);
+            prop_assert!(
+                solved.is_ok(),
+                "{} for bitboard {} with {} rules",
+                solved.unwrap_err(),
+                grid.as_bitboard(),
+                ruleset
+            );
+            prop_assert!(solved.unwrap().compare(&grid).wrong.is_empty());
+        }
+    }
+
+    #[test]
+    fn known_cells_are_kept() {
+        let grid = AtomGrid::from_bitboard(35184640598018);
+        let observations = Observations::observe_all(&grid, Ruleset::Classic);
+        let known = UncertainGrid::from_known(&grid, &AtomGrid::default()).unwrap();
+        let solved = solve_from(known, &observations).unwrap();
+        for v in grid.atoms() {
+            assert_eq!(solved.get(v), Atom);
+        }
+        // The rules still find the same empty cells.
+        let plain = solve_as_much_as_you_can(&observations);
+        for x in 0..GRID_SIZE as i8 {
+            for y in 0..GRID_SIZE as i8 {
+                let v = I8Vec2::new(x, y);
+                assert_eq!(solved.is_empty(v), plain.is_empty(v));
+            }
+        }
+    }
+
+    #[test]
+    fn queries() {
+        let grid = AtomGrid::from_bitboard(35184640598018);
+        let observations = Observations::observe_all(&grid, Ruleset::Classic);
+        let knowledge = solve_as_much_as_you_can(&observations);
+        assert_eq!(knowledge.count(Atom), 0);
+        assert_eq!(
+            knowledge.count(Empty) + knowledge.count(Unknown),
+            GRID_SIZE * GRID_SIZE
+        );
+        assert_eq!(knowledge.to_atom_grid(), None);
+        assert_eq!(knowledge.get(I8Vec2::new(-1, 3)), Empty);
+
+        let comparison = knowledge.compare(&grid);
+        assert!(comparison.wrong.is_empty());
+        assert_eq!(comparison.correct.len(), knowledge.count(Empty));
+        assert_eq!(
+            comparison.unknown,
+            knowledge.unknown_cells().collect::<Vec<_>>()
+        );
+
+        // Everything known, but one atom is misplaced.
+        let mut wrong = grid;
+        let v = grid.atoms().next().unwrap();
+        wrong.set(v, false);
+        wrong.set(I8Vec2::new(7, 7), true);
+        let empty = AtomGrid::from_bits(!wrong.bits());
+        let known = UncertainGrid::from_known(&wrong, &empty).unwrap();
+        assert_eq!(known.to_atom_grid(), Some(wrong));
+        let comparison = known.compare(&grid);
+        assert_eq!(comparison.wrong, vec![v, I8Vec2::new(7, 7)]);
+        assert_eq!(comparison.correct.len(), GRID_SIZE * GRID_SIZE - 2);
+        assert!(comparison.unknown.is_empty());
+    }
+
+    #[test]
+    fn deductions_are_counted() {
+        let grid = AtomGrid::from_bitboard(54043333103714304);
+        let observations = Observations::observe_all(&grid, Ruleset::Classic);
+        let (knowledge, deductions) =
+            solve_counting(UncertainGrid::default(), &observations).unwrap();
+        let total: usize = deductions.iter().map(|(_, count)| count).sum();
+        assert_eq!(total, knowledge.count(Empty));
+        assert_eq!(deductions[0].0, Rule::LetterFindsFourEmptySpaces);
+        assert!(deductions[0].1 > 0);
+    }
+
+    #[test]
+    fn contradictions_are_reported() {
+        let v = I8Vec2::new(2, 3);
+        let mut cells = AtomGrid::default();
+        cells.set(v, true);
+        assert_eq!(
+            UncertainGrid::from_known(&cells, &cells),
+            Err(Inconsistency {
+                position: v,
+                previous: Atom,
+                knowledge: Empty
+            })
+        );
+
+        let mut grid = UncertainGrid::default();
+        assert!(grid.set_atom(I8Vec2::new(-1, 0)).is_err());
+
+        // A reflection right at the first cell means that it is empty.
+        let mut observations = Observations::new(Ruleset::Classic);
+        let mut atoms = AtomGrid::default();
+        atoms.set(I8Vec2::new(1, 0), true);
+        observations.probe(LaserTip::new(0, Down), &atoms);
+        let mut known = UncertainGrid::default();
+        known.set_atom(I8Vec2::new(0, 0)).unwrap();
+        let error = solve_from(known, &observations).unwrap_err();
+        assert_eq!(error.position, I8Vec2::new(0, 0));
+    }
+
+    #[test]
+    fn absorption_rule_runs_until_nothing_changes() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut needed_another_pass = false;
+        for _ in 0..500 {
+            let atoms = AtomGrid::random_with(5, &mut rng);
+            let observations = Observations::observe_all(&atoms, Ruleset::Classic);
+            let solved = solve_as_much_as_you_can(&observations);
+
+            let mut again = solved.clone();
+            absorption_with_one_free_field(&mut again, &observations).unwrap();
+            assert_eq!(again, solved);
+
+            let mut once = UncertainGrid::default();
+            letter_finds_four_empty_spaces(&mut once, &observations).unwrap();
+            reflection_is_not_blocked(&mut once, &observations).unwrap();
+            absorption_with_one_free_field(&mut once, &observations).unwrap();
+            needed_another_pass |= once != solved;
         }
+        assert!(needed_another_pass);
     }
 }
@@ -0,0 +1,259 @@
+//! Draws puzzles as SVG images for printing. The layout matches [crate::observation::draw]: the
+//! grid in the middle and the observations in a ring of cells around it.
+
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::i8vec2::I8Vec2;
+use crate::laser::{LaserPath, LaserTip, TraversalOutcome};
+use crate::observation::Observations;
+use crate::solver::{GridKnowledge, UncertainGrid};
+use std::fmt::Write;
+
+/// Sizes and colors. Colors are anything SVG understands, e.g. `#ff8800` or `teal`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Style {
+    /// Width and height of a cell in pixels.
+    pub cell_size: f64,
+    /// Fill of the whole picture.
+    pub background: String,
+    /// Color of the grid lines.
+    pub line_color: String,
+    /// Color of the observations around the grid.
+    pub clue_color: String,
+    /// Fill of atoms, and of cells known to hold one.
+    pub atom_color: String,
+    /// Fill of cells known to be empty.
+    pub empty_color: String,
+    /// Fill of cells nothing is known about.
+    pub unknown_color: String,
+    /// Laser paths take the next color each, starting over after the last one.
+    pub path_colors: Vec<String>,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            cell_size: 40.0,
+            background: "white".to_owned(),
+            line_color: "#444444".to_owned(),
+            clue_color: "black".to_owned(),
+            atom_color: "#222222".to_owned(),
+            empty_color: "#e8e8e8".to_owned(),
+            unknown_color: "white".to_owned(),
+            path_colors: ["#d62728", "#1f77b4", "#2ca02c", "#ff7f0e", "#9467bd"]
+                .map(str::to_owned)
+                .to_vec(),
+        }
+    }
+}
+
+/// What goes into the picture. Only the observations are required.
+pub struct Drawing<'a> {
+    /// Drawn around the grid.
+    pub observations: &'a Observations,
+    /// The atoms, for printing the solution.
+    pub solution: Option<&'a AtomGrid>,
+    /// What the solver or a player knows about the cells, shown by shading them.
+    pub knowledge: Option<&'a UncertainGrid>,
+    /// Laser paths drawn on top, each in its own color.
+    pub paths: &'a [LaserPath],
+}
+
+/// Renders a standalone SVG document.
+pub fn render(drawing: &Drawing, style: &Style) -> Result<String, std::fmt::Error> {
+    let cs = style.cell_size;
+    let size = cs * (GRID_SIZE + 2) as f64;
+    let mut f = String::new();
+    writeln!(
+        f,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{size}" height="{size}" viewBox="0 0 {size} {size}">"#
+    )?;
+    writeln!(
+        f,
+        r#"<rect width="{size}" height="{size}" fill="{}"/>"#,
+        escape(&style.background)
+    )?;
+
+    // Cells, shaded by what is known about them.
+    for y in 0..GRID_SIZE {
+        for x in 0..GRID_SIZE {
+            let fill = match drawing
+                .knowledge
+                .map(|k| k.get(I8Vec2::new(x as i8, y as i8)))
+            {
+                Some(GridKnowledge::Empty) => &style.empty_color,
+                Some(GridKnowledge::Atom) => &style.atom_color,
+                Some(GridKnowledge::Unknown) | None => &style.unknown_color,
+            };
+            writeln!(
+                f,
+                r#"<rect x="{}" y="{}" width="{cs}" height="{cs}" fill="{}"/>"#,
+                (x + 1) as f64 * cs,
+                (y + 1) as f64 * cs,
+                escape(fill)
+            )?;
+        }
+    }
+
+    // Grid lines.
+    let (start, end) = (cs, cs * (GRID_SIZE + 1) as f64);
+    write!(
+        f,
+        r#"<g stroke="{}" stroke-width="{}">"#,
+        escape(&style.line_color),
+        cs / 40.0
+    )?;
+    for i in 0..=GRID_SIZE {
+        let at = (i + 1) as f64 * cs;
+        write!(
+            f,
+            r#"<line x1="{start}" y1="{at}" x2="{end}" y2="{at}"/><line x1="{at}" y1="{start}" x2="{at}" y2="{end}"/>"#
+        )?;
+    }
+    writeln!(f, "</g>")?;
+
+    // Observations in the ring around the grid.
+    write!(
+        f,
+        r#"<g font-family="sans-serif" font-size="{}" text-anchor="middle" dominant-baseline="central" fill="{}">"#,
+        cs * 0.6,
+        escape(&style.clue_color)
+    )?;
+    for (direction, shift, obs) in drawing.observations.iter() {
+        let position = LaserTip::new(shift, direction).position();
+        let (x, y) = center(position, cs);
+        write!(
+            f,
+            r#"<text x="{x}" y="{y}">{}</text>"#,
+            escape(&obs.to_string())
+        )?;
+    }
+    writeln!(f, "</g>")?;
+
+    if let Some(solution) = drawing.solution {
+        for v in solution.atoms() {
+            let (x, y) = center(v, cs);
+            writeln!(
+                f,
+                r#"<circle cx="{x}" cy="{y}" r="{}" fill="{}"/>"#,
+                cs * 0.35,
+                escape(&style.atom_color)
+            )?;
+        }
+    }
+
+    for (i, path) in drawing.paths.iter().enumerate() {
+        let color = style
+            .path_colors
+            .get(i % style.path_colors.len().max(1))
+            .map_or("red", |c| c.as_str());
+        write_path(&mut f, path, color, cs)?;
+    }
+
+    writeln!(f, "</svg>")?;
+    Ok(f)
+}
+
+/// Draws the laser as a line through the centers of the cells it visits, with a dot where it
+/// enters and a cross if it is absorbed.
+fn write_path(
+    f: &mut String,
+    path: &LaserPath,
+    color: &str,
+    cs: f64,
+) -> Result<(), std::fmt::Error> {
+    let color = escape(color);
+    let points: Vec<String> = path
+        .tips
+        .iter()
+        .map(|tip| {
+            let (x, y) = center(tip.position(), cs);
+            format!("{x},{y}")
+        })
+        .collect();
+    writeln!(
+        f,
+        r#"<polyline points="{}" fill="none" stroke="{color}" stroke-width="{}" stroke-linejoin="round" opacity="0.8"/>"#,
+        points.join(" "),
+        cs / 10.0
+    )?;
+    let (x, y) = center(path.tips[0].position(), cs);
+    writeln!(
+        f,
+        r#"<circle cx="{x}" cy="{y}" r="{}" fill="{color}"/>"#,
+        cs / 8.0
+    )?;
+    if let TraversalOutcome::Absorbed { .. } = path.outcome {
+        let last = path.tips[path.tips.len() - 1];
+        let (x, y) = center(last.position() + last.direction().dxy(), cs);
+        let d = cs / 5.0;
+        writeln!(
+            f,
+            r#"<path d="M{} {} L{} {} M{} {} L{} {}" stroke="{color}" stroke-width="{}"/>"#,
+            x - d,
+            y - d,
+            x + d,
+            y + d,
+            x - d,
+            y + d,
+            x + d,
+            y - d,
+            cs / 10.0
+        )?;
+    }
+    Ok(())
+}
+
+/// The center of a cell, where the border around the grid starts at -1.
+fn center(v: I8Vec2, cs: f64) -> (f64, f64) {
+    ((v.x as f64 + 1.5) * cs, (v.y as f64 + 1.5) * cs)
+}
+
+/// Makes text safe to put into an attribute or element, of SVG or HTML.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laser::{Direction, Ruleset};
+
+    #[test]
+    fn everything_is_drawn() {
+        let grid = AtomGrid::from_bitboard(35184640598018);
+        let observations = Observations::observe_all(&grid, Ruleset::Classic);
+        let knowledge = crate::solver::solve_as_much_as_you_can(&observations);
+        let paths = [
+            LaserTip::new(3, Direction::Right).record_path(&grid, Ruleset::Classic),
+            LaserTip::new(5, Direction::Up).record_path(&grid, Ruleset::Classic),
+        ];
+        let style = Style {
+            atom_color: "teal".to_owned(),
+            ..Style::default()
+        };
+        let svg = render(
+            &Drawing {
+                observations: &observations,
+                solution: Some(&grid),
+                knowledge: Some(&knowledge),
+                paths: &paths,
+            },
+            &style,
+        )
+        .unwrap();
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<text").count(), 4 * GRID_SIZE);
+        assert_eq!(svg.matches(r#"fill="teal"/>"#).count(), 5);
+        assert_eq!(svg.matches("<polyline").count(), 2);
+    }
+
+    #[test]
+    fn colors_are_escaped() {
+        assert_eq!(escape(r#"red" onload="x"#), "red&quot; onload=&quot;x");
+    }
+}
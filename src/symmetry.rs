@@ -0,0 +1,218 @@
+//! The eight rotations and reflections of the square box. The laser rules don't prefer any side or
+//! turning direction, so a transformed puzzle is really the same puzzle. The canonical form picks
+//! one representative out of the eight variants, e.g. to find duplicates in a puzzle collection.
+
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::i8vec2::I8Vec2;
+use crate::laser::{Direction, LaserTip};
+use crate::observation::{Observations, NOT_PROBED};
+use crate::puzzle::Puzzle;
+use std::fmt::{Display, Formatter};
+
+/// A rotation or reflection of the box. Rotations are clockwise as seen on the screen.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    /// Leaves everything as it is.
+    Identity,
+    /// Rotates by a quarter turn.
+    Rotate90,
+    /// Rotates by half a turn.
+    Rotate180,
+    /// Rotates by three quarter turns.
+    Rotate270,
+    /// Swaps the left and the right side.
+    MirrorHorizontal,
+    /// Swaps the top and the bottom side.
+    MirrorVertical,
+    /// Mirrors along the diagonal from the top left to the bottom right.
+    Transpose,
+    /// Mirrors along the diagonal from the top right to the bottom left.
+    AntiTranspose,
+}
+
+impl Symmetry {
+    /// All eight, starting with [Symmetry::Identity].
+    pub const fn all() -> [Symmetry; 8] {
+        [
+            Symmetry::Identity,
+            Symmetry::Rotate90,
+            Symmetry::Rotate180,
+            Symmetry::Rotate270,
+            Symmetry::MirrorHorizontal,
+            Symmetry::MirrorVertical,
+            Symmetry::Transpose,
+            Symmetry::AntiTranspose,
+        ]
+    }
+
+    /// Moves a position. Works for the border around the grid as well.
+    pub fn apply(self, v: I8Vec2) -> I8Vec2 {
+        let last = GRID_SIZE as i8 - 1;
+        let (x, y) = (v.x, v.y);
+        match self {
+            Symmetry::Identity => I8Vec2::new(x, y),
+            Symmetry::Rotate90 => I8Vec2::new(last - y, x),
+            Symmetry::Rotate180 => I8Vec2::new(last - x, last - y),
+            Symmetry::Rotate270 => I8Vec2::new(y, last - x),
+            Symmetry::MirrorHorizontal => I8Vec2::new(last - x, y),
+            Symmetry::MirrorVertical => I8Vec2::new(x, last - y),
+            Symmetry::Transpose => I8Vec2::new(y, x),
+            Symmetry::AntiTranspose => I8Vec2::new(last - y, last - x),
+        }
+    }
+
+    /// The direction a laser moves in after the transformation.
+    pub fn apply_direction(self, direction: Direction) -> Direction {
+        let origin = I8Vec2::new(0, 0);
+        let dxy = self.apply(direction.dxy()) - self.apply(origin);
+        Direction::all()
+            .into_iter()
+            .find(|d| d.dxy() == dxy)
+            .expect("Symmetries map directions onto directions.")
+    }
+
+    /// Moves a laser entering from the border, given as the constructor parameters of [LaserTip].
+    pub fn apply_entry(self, shift: u8, direction: Direction) -> (u8, Direction) {
+        let position = self.apply(LaserTip::new(shift, direction).position());
+        let direction = self.apply_direction(direction);
+        match direction {
+            Direction::Up | Direction::Down => (position.x as u8, direction),
+            Direction::Left | Direction::Right => (position.y as u8, direction),
+        }
+    }
+}
+
+impl Display for Symmetry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Symmetry::Identity => "identity",
+            Symmetry::Rotate90 => "rotate-90",
+            Symmetry::Rotate180 => "rotate-180",
+            Symmetry::Rotate270 => "rotate-270",
+            Symmetry::MirrorHorizontal => "mirror-horizontal",
+            Symmetry::MirrorVertical => "mirror-vertical",
+            Symmetry::Transpose => "transpose",
+            Symmetry::AntiTranspose => "anti-transpose",
+        })
+    }
+}
+
+/// Anything living in the box which can be rotated and mirrored.
+pub trait Transform: Sized {
+    /// A copy moved by the symmetry.
+    fn transformed(&self, symmetry: Symmetry) -> Self;
+}
+
+/// The smallest of all eight variants, together with the symmetry that leads there.
+pub fn canonical<T: Transform + Ord>(value: &T) -> (Symmetry, T) {
+    Symmetry::all()
+        .into_iter()
+        .map(|symmetry| (symmetry, value.transformed(symmetry)))
+        .min_by(|(_, a), (_, b)| a.cmp(b))
+        .expect("There is always the identity.")
+}
+
+impl Transform for AtomGrid {
+    fn transformed(&self, symmetry: Symmetry) -> Self {
+        let mut grid = AtomGrid::default();
+        for v in self.atoms() {
+            grid.set(symmetry.apply(v), true);
+        }
+        grid
+    }
+}
+
+impl Transform for Observations {
+    /// Letters are renamed afterwards, so the result compares equal to observing the transformed
+    /// grid.
+    fn transformed(&self, symmetry: Symmetry) -> Self {
+        let mut sides = [[NOT_PROBED; GRID_SIZE]; 4];
+        for (direction, shift, obs) in self.iter() {
+            let (shift, direction) = symmetry.apply_entry(shift, direction);
+            sides[direction as usize][shift as usize] = obs;
+        }
+        Observations::from_sides(sides, self.ruleset).expect("Letters stay in pairs.")
+    }
+}
+
+impl Transform for Puzzle {
+    fn transformed(&self, symmetry: Symmetry) -> Self {
+        Puzzle {
+            atom_count: self.atom_count,
+            observations: self.observations.transformed(symmetry),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laser::Ruleset;
+    use crate::solver;
+
+    #[test]
+    fn observations_follow_the_grid() {
+        for ruleset in [Ruleset::Classic, Ruleset::Plain] {
+            for _ in 0..20 {
+                let grid = AtomGrid::random(5);
+                let observations = Observations::observe_all(&grid, ruleset);
+                for symmetry in Symmetry::all() {
+                    assert_eq!(
+                        Observations::observe_all(&grid.transformed(symmetry), ruleset),
+                        observations.transformed(symmetry),
+                        "{} with {}",
+                        grid.as_bitboard(),
+                        symmetry
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn solver_does_not_care_about_symmetry() {
+        for _ in 0..20 {
+            let grid = AtomGrid::random(4);
+            let observations = Observations::observe_all(&grid, Ruleset::Classic);
+            let knowledge = solver::solve_as_much_as_you_can(&observations);
+            for symmetry in Symmetry::all() {
+                assert_eq!(
+                    solver::solve_as_much_as_you_can(&observations.transformed(symmetry)),
+                    knowledge.transformed(symmetry),
+                    "{} with {}",
+                    grid.as_bitboard(),
+                    symmetry
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn all_variants_have_the_same_canonical_form() {
+        let grid = AtomGrid::random(5);
+        let puzzle = Puzzle::from_grid(&grid, 5, Ruleset::Classic);
+        let (_, canonical_grid) = canonical(&grid);
+        let (_, canonical_puzzle) = canonical(&puzzle);
+        for symmetry in Symmetry::all() {
+            assert_eq!(canonical(&grid.transformed(symmetry)).1, canonical_grid);
+            assert_eq!(canonical(&puzzle.transformed(symmetry)).1, canonical_puzzle);
+        }
+        let (symmetry, canonical_puzzle) = canonical(&puzzle);
+        assert_eq!(puzzle.transformed(symmetry), canonical_puzzle);
+    }
+
+    #[test]
+    fn four_quarter_turns_are_no_turn() {
+        let grid = AtomGrid::random(5);
+        let mut turned = grid;
+        for _ in 0..4 {
+            turned = turned.transformed(Symmetry::Rotate90);
+        }
+        assert_eq!(turned, grid);
+        assert_eq!(
+            grid.transformed(Symmetry::Rotate90)
+                .transformed(Symmetry::Rotate90),
+            grid.transformed(Symmetry::Rotate180)
+        );
+    }
+}
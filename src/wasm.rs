@@ -0,0 +1,310 @@
+//! Bindings for JavaScript, packaged with `wasm-pack build -- --features wasm`.
+//!
+//! Everything crosses the boundary as numbers, strings and JSON: cells are `[x, y]` pairs, sides
+//! of the box are named `top`, `bottom`, `left` and `right` like on the command line, and rules
+//! are `classic` or `plain`. Puzzles go in as their text format and come out as the same JSON the
+//! command line prints.
+
+use crate::atom_grid::{AtomGrid, GRID_SIZE};
+use crate::generator::{self, Difficulty};
+use crate::i8vec2::I8Vec2;
+use crate::json::{
+    atoms_json, knowledge_json, parse_atoms, parse_side, puzzle_json, side_name, sides_json,
+};
+use crate::laser::{LaserTip, Ruleset};
+use crate::observation::{self, Observations, ProbeResult};
+use crate::puzzle::Puzzle;
+use crate::{sat, solver};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+/// The atoms hidden in the box.
+#[wasm_bindgen(js_name = AtomGrid)]
+pub struct Grid {
+    grid: AtomGrid,
+}
+
+#[wasm_bindgen(js_class = AtomGrid)]
+impl Grid {
+    /// An empty box.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Grid {
+        Grid {
+            grid: AtomGrid::default(),
+        }
+    }
+
+    /// A box with atoms in random cells, the same ones for the same seed. Fails for more atoms
+    /// than cells.
+    pub fn random(atoms: u8, seed: u32) -> Result<Grid, JsError> {
+        Ok(Grid {
+            grid: random_grid(atoms, seed).map_err(|e| JsError::new(&e))?,
+        })
+    }
+
+    /// A box with atoms in the cells of a JSON list like `[[0, 1], [5, 3]]`.
+    #[wasm_bindgen(js_name = fromAtoms)]
+    pub fn from_atoms(atoms: &str) -> Result<Grid, JsError> {
+        let json: Value = serde_json::from_str(atoms)?;
+        Ok(Grid {
+            grid: parse_atoms(&json).map_err(|e| JsError::new(&e))?,
+        })
+    }
+
+    /// Whether there is an atom. Cells outside the box never have one.
+    pub fn get(&self, x: i32, y: i32) -> bool {
+        cell(x, y).is_ok_and(|v| self.grid.get(v))
+    }
+
+    /// Puts an atom into a cell or takes it out. Fails for cells outside the box.
+    pub fn set(&mut self, x: i32, y: i32, atom: bool) -> Result<(), JsError> {
+        self.grid
+            .set(cell(x, y).map_err(|e| JsError::new(&e))?, atom);
+        Ok(())
+    }
+
+    /// The cells with atoms as a JSON list of `[x, y]`, row by row.
+    pub fn atoms(&self) -> String {
+        atoms_json(&self.grid).to_string()
+    }
+
+    /// Shines a laser in and tells what comes out, e.g. `{"result":"absorbed"}` or
+    /// `{"result":"exit","side":"right","shift":3}`.
+    pub fn probe(&self, side: &str, shift: u8, rules: &str) -> Result<String, JsError> {
+        self.probe_json(side, shift, rules)
+            .map(|json| json.to_string())
+            .map_err(|e| JsError::new(&e))
+    }
+
+    /// The cells a laser passes, as a JSON list of `[x, y]` starting on the border where it is
+    /// shone in. Cells outside the box have coordinates -1 or 8.
+    pub fn path(&self, side: &str, shift: u8, rules: &str) -> Result<String, JsError> {
+        self.path_json(side, shift, rules)
+            .map(|json| json.to_string())
+            .map_err(|e| JsError::new(&e))
+    }
+
+    /// Shines lasers in from everywhere and returns what the player sees: a JSON object with the
+    /// symbols of each side.
+    pub fn observe(&self, rules: &str) -> Result<String, JsError> {
+        let ruleset = parse_rules(rules).map_err(|e| JsError::new(&e))?;
+        Ok(sides_json(&Observations::observe_all(&self.grid, ruleset)).to_string())
+    }
+
+    /// The fully probed puzzle for these atoms, as JSON with the text format under `text`.
+    pub fn puzzle(&self, rules: &str) -> Result<String, JsError> {
+        let ruleset = parse_rules(rules).map_err(|e| JsError::new(&e))?;
+        let puzzle = Puzzle::from_grid(&self.grid, self.grid.atom_count() as u8, ruleset);
+        Ok(puzzle_json(&puzzle).to_string())
+    }
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Grid::new()
+    }
+}
+
+impl Grid {
+    fn probe_json(&self, side: &str, shift: u8, rules: &str) -> Result<Value, String> {
+        let laser = LaserTip::new(parse_shift(shift)?, parse_side(side)?);
+        Ok(
+            match observation::shoot(laser, &self.grid, parse_rules(rules)?) {
+                ProbeResult::Absorbed => json!({ "result": "absorbed" }),
+                ProbeResult::Reflected => json!({ "result": "reflected" }),
+                ProbeResult::Exit(out_shift, out_direction) => json!({
+                    "result": "exit",
+                    "side": side_name(out_direction),
+                    "shift": out_shift,
+                }),
+            },
+        )
+    }
+
+    fn path_json(&self, side: &str, shift: u8, rules: &str) -> Result<Value, String> {
+        let laser = LaserTip::new(parse_shift(shift)?, parse_side(side)?);
+        let path = laser.record_path(&self.grid, parse_rules(rules)?);
+        Ok(path
+            .tips
+            .iter()
+            .map(|tip| json!([tip.position().x, tip.position().y]))
+            .collect())
+    }
+}
+
+/// A random puzzle with a unique solution, as JSON with the `puzzle`, its `difficulty` and the
+/// `solution` atoms. Leave the difficulty out (`undefined`) to accept any.
+#[wasm_bindgen]
+pub fn generate(
+    atoms: u8,
+    rules: &str,
+    difficulty: Option<String>,
+    seed: u32,
+) -> Result<String, JsError> {
+    generate_json(atoms, rules, difficulty.as_deref(), seed)
+        .map(|json| json.to_string())
+        .map_err(|e| JsError::new(&e))
+}
+
+/// Solves a puzzle given in the text format. The JSON result has what the rules find out under
+/// `knowledge`, one string per row with `o`, `.` and `?`, and up to two `solutions`.
+#[wasm_bindgen]
+pub fn solve(puzzle: &str) -> Result<String, JsError> {
+    solve_json(puzzle)
+        .map(|json| json.to_string())
+        .map_err(|e| JsError::new(&e))
+}
+
+fn generate_json(
+    atoms: u8,
+    rules: &str,
+    difficulty: Option<&str>,
+    seed: u32,
+) -> Result<Value, String> {
+    let difficulty = difficulty.map(str::parse::<Difficulty>).transpose()?;
+    let mut rng = StdRng::seed_from_u64(seed as u64);
+    let (grid, puzzle) = generator::generate(atoms, parse_rules(rules)?, difficulty, &mut rng)
+        .ok_or("no puzzle with a unique solution found, try fewer atoms or another seed")?;
+    Ok(json!({
+        "puzzle": puzzle_json(&puzzle),
+        "difficulty": Difficulty::of(&puzzle).to_string(),
+        "solution": atoms_json(&grid),
+    }))
+}
+
+fn solve_json(puzzle: &str) -> Result<Value, String> {
+    let puzzle: Puzzle = puzzle.parse().map_err(|e| format!("{}", e))?;
+    let knowledge = solver::solve_as_much_as_you_can(&puzzle.observations);
+    // The brute force search would hang the page for puzzles with many atoms.
+    let solutions: Vec<Value> = sat::find_solutions(&puzzle, 2)
+        .iter()
+        .map(atoms_json)
+        .collect();
+    Ok(json!({
+        "knowledge": knowledge_json(&knowledge),
+        "unique": solutions.len() == 1,
+        "solutions": solutions,
+    }))
+}
+
+fn random_grid(atoms: u8, seed: u32) -> Result<AtomGrid, String> {
+    if atoms as usize > GRID_SIZE * GRID_SIZE {
+        return Err(format!("{} atoms don't fit into the box", atoms));
+    }
+    Ok(AtomGrid::random_with(
+        atoms,
+        &mut StdRng::seed_from_u64(seed as u64),
+    ))
+}
+
+fn cell(x: i32, y: i32) -> Result<I8Vec2, String> {
+    let size = GRID_SIZE as i32;
+    if (0..size).contains(&x) && (0..size).contains(&y) {
+        Ok(I8Vec2::new(x as i8, y as i8))
+    } else {
+        Err(format!("cell {},{} is outside the box", x, y))
+    }
+}
+
+fn parse_rules(rules: &str) -> Result<Ruleset, String> {
+    rules.parse()
+}
+
+fn parse_shift(shift: u8) -> Result<u8, String> {
+    if (shift as usize) < GRID_SIZE {
+        Ok(shift)
+    } else {
+        Err(format!("shift must be below {}", GRID_SIZE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laser::Direction;
+
+    fn grid() -> Grid {
+        Grid {
+            grid: AtomGrid::from_bitboard(35184640598018),
+        }
+    }
+
+    #[test]
+    fn atoms_go_in_and_out_as_cells() {
+        let grid = grid();
+        let atoms: Value = serde_json::from_str(&grid.atoms()).unwrap();
+        assert_eq!(parse_atoms(&atoms), Ok(grid.grid));
+        let first = grid.grid.atoms().next().unwrap();
+        assert!(grid.get(first.x as i32, first.y as i32));
+        assert!(!grid.get(-1, 3) && !grid.get(3, 8));
+    }
+
+    #[test]
+    fn random_grids_fit_into_the_box() {
+        assert_eq!(random_grid(5, 1).unwrap().atom_count(), 5);
+        assert_eq!(random_grid(64, 1).unwrap().atom_count(), 64);
+        assert!(random_grid(65, 1).is_err());
+    }
+
+    #[test]
+    fn probes_match_the_observations() {
+        let grid = grid();
+        let observations = Observations::observe_all(&grid.grid, Ruleset::Classic);
+        for direction in Direction::all() {
+            for shift in 0..GRID_SIZE as u8 {
+                let json = grid
+                    .probe_json(side_name(direction), shift, "classic")
+                    .unwrap();
+                let obs = observations.sides[direction as usize][shift as usize];
+                match json["result"].as_str().unwrap() {
+                    "absorbed" => assert_eq!(obs, observation::LASER_ABSORBED),
+                    "reflected" => assert_eq!(obs, observation::LASER_REFLECTED),
+                    _ => {
+                        let out = parse_side(json["side"].as_str().unwrap()).unwrap();
+                        let out_shift = json["shift"].as_u64().unwrap() as usize;
+                        assert_eq!(observations.sides[out as usize][out_shift], obs);
+                    }
+                }
+            }
+        }
+        assert!(grid.probe_json("front", 0, "classic").is_err());
+        assert!(grid.probe_json("top", 8, "classic").is_err());
+        assert!(grid.probe_json("top", 0, "fancy").is_err());
+    }
+
+    #[test]
+    fn path_starts_on_the_border() {
+        let path = grid().path_json("left", 3, "classic").unwrap();
+        assert_eq!(path[0], json!([-1, 3]));
+        assert_eq!(path[1], json!([0, 3]));
+    }
+
+    #[test]
+    fn generated_puzzles_solve() {
+        let generated = generate_json(4, "classic", Some("easy"), 7).unwrap();
+        assert_eq!(generated["difficulty"], "easy");
+        let text = generated["puzzle"]["text"].as_str().unwrap();
+        let solved = solve_json(text).unwrap();
+        assert_eq!(solved["unique"], true);
+        assert_eq!(solved["solutions"][0], generated["solution"]);
+        assert!(generate_json(4, "classic", Some("impossible"), 7).is_err());
+        assert!(solve_json("not a puzzle").is_err());
+    }
+
+    #[test]
+    fn puzzles_with_many_atoms_solve() {
+        let grid = random_grid(12, 3).unwrap();
+        let puzzle = Puzzle::from_grid(&grid, 12, Ruleset::Classic);
+        let solved = solve_json(&puzzle.to_string()).unwrap();
+        let solutions = solved["solutions"].as_array().unwrap();
+        assert!(!solutions.is_empty());
+        for solution in solutions {
+            let solution = parse_atoms(solution).unwrap();
+            assert_eq!(solution.atom_count(), 12);
+            assert!(puzzle.observations.is_explained_by(&solution));
+        }
+        assert_eq!(solved["unique"], solutions.len() == 1);
+    }
+}